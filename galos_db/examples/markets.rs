@@ -0,0 +1,19 @@
+use galos_db::{Error, Database};
+use galos_db::{markets::MarketPrice, systems::System};
+
+#[async_std::main]
+async fn main() -> Result<(), Error> {
+    let db = Database::new().await?;
+    let sol = System::fetch_by_name(&db, "Sol").await?
+        .into_iter()
+        .next()
+        .ok_or(Error::Sqlx(sqlx::Error::RowNotFound))?;
+
+    let buys = MarketPrice::fetch_best_buys(&db, sol.address as u64, 50., "gold", 5).await?;
+    println!("BUY: {:#?}", buys);
+
+    let sells = MarketPrice::fetch_best_sells(&db, sol.address as u64, 50., "gold", 5).await?;
+    println!("SELL: {:#?}", sells);
+
+    Ok(())
+}
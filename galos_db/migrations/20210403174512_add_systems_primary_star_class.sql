@@ -0,0 +1 @@
+ALTER TABLE systems ADD COLUMN primary_star_class varchar;
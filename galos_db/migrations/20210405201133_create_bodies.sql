@@ -0,0 +1,43 @@
+CREATE TYPE BodyType AS ENUM (
+    'Star',
+    'Planet',
+    'Belt'
+);
+
+/* NOTE: The system isn't a foreign key, since EDDN often sends a system's bodies before the
+ * system itself. */
+CREATE TABLE bodies (
+    system_address       bigint            NOT NULL,
+    id                   smallint          NOT NULL,
+    name                 varchar           NOT NULL,
+    type                 BodyType          NOT NULL,
+    star_class           varchar,
+    planet_class         varchar,
+    /* Light seconds from the arrival star. */
+    distance_to_arrival  double precision  NOT NULL,
+    landable             boolean,
+    /* Surface gravity in g. */
+    gravity              double precision,
+    terraform_state      varchar,
+    atmosphere           varchar,
+    updated_at           timestamp         NOT NULL,
+
+    PRIMARY KEY (system_address, id)
+);
+
+CREATE INDEX ON bodies ((upper(name)));
+
+CREATE TABLE body_rings (
+    system_address  bigint            NOT NULL,
+    body_id         smallint          NOT NULL,
+    name            varchar           NOT NULL,
+    class           varchar           NOT NULL,
+    inner_radius    double precision  NOT NULL,
+    outer_radius    double precision  NOT NULL,
+    mass            double precision  NOT NULL,
+
+    PRIMARY KEY (system_address, body_id, name),
+    FOREIGN KEY (system_address, body_id)
+    REFERENCES bodies (system_address, id)
+    ON DELETE CASCADE
+);
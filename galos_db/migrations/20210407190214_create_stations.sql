@@ -0,0 +1,28 @@
+CREATE TYPE LandingPad AS ENUM (
+    'Small',
+    'Medium',
+    'Large'
+);
+
+/* NOTE: Fleet carriers are stations too, but their system changes as they jump. The system
+ * isn't a foreign key, since EDDN often sends a station before its system. */
+CREATE TABLE stations (
+    market_id            bigint            PRIMARY KEY,
+    system_address       bigint            NOT NULL,
+    name                 varchar           NOT NULL,
+    type                 varchar,
+    /* Light seconds from the arrival star. */
+    distance_to_arrival  double precision,
+    /* The largest landing pad. */
+    landing_pad          LandingPad,
+    services             text[]            NOT NULL DEFAULT '{}',
+    faction_id           integer           REFERENCES factions,
+    government           Government,
+    allegiance           Allegiance,
+    primary_economy      Economy,
+    secondary_economy    Economy,
+    updated_at           timestamp         NOT NULL
+);
+
+CREATE INDEX ON stations (system_address);
+CREATE INDEX ON stations ((upper(name)));
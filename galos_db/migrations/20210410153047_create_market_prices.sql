@@ -0,0 +1,15 @@
+/* NOTE: There is no foreign key on stations, since prices for a market can arrive before we
+ * know anything else about its station. */
+CREATE TABLE market_prices (
+    market_id   bigint     NOT NULL,
+    commodity   varchar    NOT NULL,
+    buy_price   integer    NOT NULL,
+    sell_price  integer    NOT NULL,
+    stock       integer    NOT NULL,
+    demand      integer    NOT NULL,
+    updated_at  timestamp  NOT NULL,
+
+    PRIMARY KEY (market_id, commodity)
+);
+
+CREATE INDEX ON market_prices (commodity);
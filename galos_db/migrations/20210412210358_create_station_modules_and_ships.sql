@@ -0,0 +1,20 @@
+/* NOTE: Like market_prices, these are keyed by market ID without a foreign key on stations. */
+CREATE TABLE station_modules (
+    market_id   bigint     NOT NULL,
+    module      varchar    NOT NULL,
+    updated_at  timestamp  NOT NULL,
+
+    PRIMARY KEY (market_id, module)
+);
+
+CREATE INDEX ON station_modules (module);
+
+CREATE TABLE station_ships (
+    market_id   bigint     NOT NULL,
+    ship        varchar    NOT NULL,
+    updated_at  timestamp  NOT NULL,
+
+    PRIMARY KEY (market_id, ship)
+);
+
+CREATE INDEX ON station_ships (ship);
@@ -0,0 +1,19 @@
+CREATE TABLE import_runs (
+    id          serial     PRIMARY KEY,
+    /* The importer, e.g. 'eddb' or 'journal'. */
+    source      varchar    NOT NULL,
+    path        varchar    NOT NULL,
+    /* SHA-256 of the first MiB of the file. */
+    file_hash   varchar    NOT NULL,
+    /* The file's size in bytes, when the run was started or last resumed. */
+    file_size   bigint     NOT NULL,
+    /* Rows, or journal entries, read so far. */
+    position    bigint     NOT NULL DEFAULT 0,
+    processed   bigint     NOT NULL DEFAULT 0,
+    failed      bigint     NOT NULL DEFAULT 0,
+    finished    boolean    NOT NULL DEFAULT false,
+    started_at  timestamp  NOT NULL,
+    updated_at  timestamp  NOT NULL
+);
+
+CREATE INDEX ON import_runs (source, path, file_hash);
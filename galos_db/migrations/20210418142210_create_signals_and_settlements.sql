@@ -0,0 +1,42 @@
+ALTER TABLE systems ADD COLUMN body_count integer;
+ALTER TABLE systems ADD COLUMN non_body_count integer;
+
+/* NOTE: Systems aren't foreign keys here, since EDDN often sends a system's signals and
+ * settlements before the system itself. */
+
+/* Signal sources found with the FSS, like stations, carriers and USSs. */
+CREATE TABLE system_signals (
+    system_address  bigint     NOT NULL,
+    name            varchar    NOT NULL,
+    is_station      boolean    NOT NULL DEFAULT false,
+    uss_type        varchar,
+    threat_level    smallint,
+    updated_at      timestamp  NOT NULL,
+    /* When a temporary signal source disappears. */
+    expires_at      timestamp,
+
+    PRIMARY KEY (system_address, name)
+);
+
+/* Surface signals found by mapping a body, like biological or geological sites. */
+CREATE TABLE body_signals (
+    system_address  bigint     NOT NULL,
+    body_id         smallint   NOT NULL,
+    type            varchar    NOT NULL,
+    count           integer    NOT NULL,
+    updated_at      timestamp  NOT NULL,
+
+    PRIMARY KEY (system_address, body_id, type)
+);
+
+CREATE TABLE settlements (
+    system_address  bigint            NOT NULL,
+    body_id         smallint          NOT NULL,
+    name            varchar           NOT NULL,
+    market_id       bigint,
+    latitude        double precision,
+    longitude       double precision,
+    updated_at      timestamp         NOT NULL,
+
+    PRIMARY KEY (system_address, body_id, name)
+);
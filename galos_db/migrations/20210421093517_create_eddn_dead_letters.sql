@@ -0,0 +1,11 @@
+/* EDDN messages which couldn't be parsed or written, kept to be reprocessed. */
+CREATE TABLE eddn_dead_letters (
+    id              serial     PRIMARY KEY,
+    schema_ref      varchar,
+    raw             text       NOT NULL,
+    error           varchar    NOT NULL,
+    received_at     timestamp  NOT NULL,
+    reprocessed_at  timestamp
+);
+
+CREATE INDEX ON eddn_dead_letters (id) WHERE reprocessed_at IS NULL;
@@ -0,0 +1,44 @@
+CREATE TYPE SystemNameKind AS ENUM (
+    'Procedural',
+    'Catalogue',
+    'Renamed',
+    'Historical'
+);
+
+/* Procedurally generated names look like "Synuefe EN-H d11-96" or "Col 285 Sector AB-C d1". */
+CREATE FUNCTION system_name_kind(name varchar) RETURNS SystemNameKind AS $$
+    SELECT CASE
+        WHEN name ~* ' [A-Z][A-Z]-[A-Z] [A-H][0-9]' THEN 'Procedural'
+        ELSE 'Catalogue'
+    END::SystemNameKind
+$$ LANGUAGE SQL IMMUTABLE;
+
+/* Every name a system is known by, including `systems.name`. Names are uppercased, like
+ * `systems.name`, and aren't unique. */
+CREATE TABLE system_names (
+    address  bigint          NOT NULL REFERENCES systems,
+    name     varchar         NOT NULL,
+    kind     SystemNameKind  NOT NULL,
+
+    PRIMARY KEY (address, name)
+);
+
+CREATE INDEX ON system_names (name);
+
+INSERT INTO system_names (address, name, kind)
+SELECT address, upper(name), system_name_kind(name)
+FROM systems;
+
+/* New systems are always known by their own name. */
+CREATE FUNCTION insert_system_name() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO system_names (address, name, kind)
+    VALUES (NEW.address, upper(NEW.name), system_name_kind(NEW.name))
+    ON CONFLICT DO NOTHING;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER insert_system_name
+AFTER INSERT ON systems
+FOR EACH ROW EXECUTE FUNCTION insert_system_name();
@@ -0,0 +1,13 @@
+/* Systems which weren't stored because another system already has their position. These are
+ * kept for review, see `galos-sync doctor`, rather than guessing which record is right. */
+CREATE TABLE system_collisions (
+    address           bigint            NOT NULL,
+    name              varchar           NOT NULL,
+    position          geometry(POINTZ)  NOT NULL,
+    existing_address  bigint            NOT NULL REFERENCES systems,
+    first_seen_at     timestamp         NOT NULL,
+    last_seen_at      timestamp         NOT NULL,
+    count             integer           NOT NULL DEFAULT 1,
+
+    PRIMARY KEY (address, existing_address)
+);
@@ -0,0 +1,79 @@
+/* Each state a system has been seen in, since `systems` is overwritten in place. A row is
+ * added when a system is created, and whenever one of these columns changes. */
+CREATE TABLE system_history (
+    address            bigint     NOT NULL REFERENCES systems,
+    population         bigint,
+    security           Security,
+    government         Government,
+    allegiance         Allegiance,
+    primary_economy    Economy,
+    secondary_economy  Economy,
+    updated_at         timestamp  NOT NULL
+);
+
+CREATE INDEX ON system_history (address, updated_at);
+
+INSERT INTO system_history
+SELECT
+    address,
+    population,
+    security,
+    government,
+    allegiance,
+    primary_economy,
+    secondary_economy,
+    updated_at
+FROM systems;
+
+CREATE FUNCTION insert_system_history()
+RETURNS TRIGGER
+AS
+$$
+BEGIN
+    IF TG_OP = 'INSERT' OR
+       (NEW.population,
+        NEW.security,
+        NEW.government,
+        NEW.allegiance,
+        NEW.primary_economy,
+        NEW.secondary_economy)
+       IS DISTINCT FROM
+       (OLD.population,
+        OLD.security,
+        OLD.government,
+        OLD.allegiance,
+        OLD.primary_economy,
+        OLD.secondary_economy)
+    THEN
+        INSERT INTO system_history (
+            address,
+            population,
+            security,
+            government,
+            allegiance,
+            primary_economy,
+            secondary_economy,
+            updated_at
+        )
+        VALUES(
+            NEW.address,
+            NEW.population,
+            NEW.security,
+            NEW.government,
+            NEW.allegiance,
+            NEW.primary_economy,
+            NEW.secondary_economy,
+            NEW.updated_at
+        );
+    END IF;
+
+    RETURN NEW;
+END
+$$
+LANGUAGE PLPGSQL;
+
+CREATE TRIGGER system_changes
+AFTER INSERT OR UPDATE
+ON systems
+FOR EACH ROW
+EXECUTE PROCEDURE insert_system_history();
@@ -0,0 +1,105 @@
+/* When each of a faction's pending, active and recovering states started and ended, since
+ * `system_faction_states` only holds the current states. The history is kept by triggers, so
+ * states cleared and written again in the same update carry on rather than restarting. */
+CREATE TABLE system_faction_state_history (
+    system_address  bigint     NOT NULL,
+    faction_id      integer    NOT NULL,
+    state           State      NOT NULL,
+    status          Status     NOT NULL,
+    started_at      timestamp  NOT NULL,
+    /* NULL while the state is current. */
+    ended_at        timestamp,
+
+    FOREIGN KEY (system_address, faction_id)
+    REFERENCES system_factions (system_address, faction_id)
+);
+
+CREATE INDEX ON system_faction_state_history (faction_id, system_address, started_at);
+
+INSERT INTO system_faction_state_history
+SELECT
+    st.system_address,
+    st.faction_id,
+    st.state,
+    st.status,
+    sf.updated_at,
+    NULL
+FROM system_faction_states st
+JOIN system_factions sf USING (system_address, faction_id);
+
+/* States are ended as of the faction's update which removed them. */
+CREATE FUNCTION end_system_faction_state()
+RETURNS TRIGGER
+AS
+$$
+BEGIN
+    UPDATE system_faction_state_history h
+    SET ended_at = sf.updated_at
+    FROM system_factions sf
+    WHERE sf.system_address = OLD.system_address
+      AND sf.faction_id = OLD.faction_id
+      AND h.system_address = OLD.system_address
+      AND h.faction_id = OLD.faction_id
+      AND h.state = OLD.state
+      AND h.status = OLD.status
+      AND h.ended_at IS NULL;
+
+    RETURN OLD;
+END
+$$
+LANGUAGE PLPGSQL;
+
+CREATE FUNCTION start_system_faction_state()
+RETURNS TRIGGER
+AS
+$$
+DECLARE
+    seen_at timestamp;
+BEGIN
+    SELECT updated_at INTO seen_at
+    FROM system_factions
+    WHERE system_address = NEW.system_address
+      AND faction_id = NEW.faction_id;
+
+    /* A state which was ended by this same update is still going. */
+    UPDATE system_faction_state_history
+    SET ended_at = NULL
+    WHERE system_address = NEW.system_address
+      AND faction_id = NEW.faction_id
+      AND state = NEW.state
+      AND status = NEW.status
+      AND ended_at = seen_at;
+
+    IF NOT FOUND THEN
+        INSERT INTO system_faction_state_history (
+            system_address,
+            faction_id,
+            state,
+            status,
+            started_at
+        )
+        VALUES(
+            NEW.system_address,
+            NEW.faction_id,
+            NEW.state,
+            NEW.status,
+            seen_at
+        );
+    END IF;
+
+    RETURN NEW;
+END
+$$
+LANGUAGE PLPGSQL;
+
+CREATE TRIGGER system_faction_state_ends
+AFTER DELETE
+ON system_faction_states
+FOR EACH ROW
+EXECUTE PROCEDURE end_system_faction_state();
+
+CREATE TRIGGER system_faction_state_starts
+AFTER INSERT
+ON system_faction_states
+FOR EACH ROW
+EXECUTE PROCEDURE start_system_faction_state();
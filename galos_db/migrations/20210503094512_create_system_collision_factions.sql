@@ -0,0 +1,18 @@
+/* Factions reported in systems which collided, which would otherwise be dropped along with the
+ * system. Only the latest report of each faction is kept, like `system_factions`. */
+CREATE TABLE system_collision_factions (
+    address           bigint      NOT NULL,
+    existing_address  bigint      NOT NULL,
+    faction_id        integer     NOT NULL REFERENCES factions,
+    state             State,
+    influence         real        NOT NULL,
+    happiness         Happiness,
+    government        Government  NOT NULL,
+    allegiance        Allegiance  NOT NULL,
+    updated_at        timestamp   NOT NULL,
+
+    PRIMARY KEY (address, existing_address, faction_id),
+    FOREIGN KEY (address, existing_address)
+    REFERENCES system_collisions (address, existing_address)
+    ON DELETE CASCADE
+);
@@ -0,0 +1,245 @@
+use chrono::{DateTime, Utc};
+use elite_journal::entry::scan::Scan;
+use crate::{Error, Database};
+use crate::systems::System;
+
+// Standard gravity, the journal reports surface gravity in m/s^2.
+const G: f64 = 9.80665;
+
+#[derive(sqlx::Type, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "bodytype")]
+pub enum BodyType {
+    Star,
+    Planet,
+    Belt,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Body {
+    pub system_address: u64,
+    pub id: u16,
+    pub name: String,
+    pub ty: BodyType,
+    pub star_class: Option<String>,
+    pub planet_class: Option<String>,
+    /// Light seconds from the arrival star.
+    pub distance_to_arrival: f64,
+    pub landable: Option<bool>,
+    /// Surface gravity in g.
+    pub gravity: Option<f64>,
+    pub terraform_state: Option<String>,
+    pub atmosphere: Option<String>,
+    pub rings: Vec<Ring>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ring {
+    pub name: String,
+    pub class: String,
+    pub inner_radius: f64,
+    pub outer_radius: f64,
+    pub mass: f64,
+}
+
+impl Body {
+    /// Inserts or updates the body and its rings, unless the stored body is newer.
+    pub async fn create(db: &Database, body: &Body) -> Result<(), Error> {
+        let row = sqlx::query!(
+            r#"
+            INSERT INTO bodies
+                (system_address,
+                 id,
+                 name,
+                 type,
+                 star_class,
+                 planet_class,
+                 distance_to_arrival,
+                 landable,
+                 gravity,
+                 terraform_state,
+                 atmosphere,
+                 updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            ON CONFLICT (system_address, id)
+            DO UPDATE SET
+                name = $3,
+                type = $4,
+                star_class = $5,
+                planet_class = $6,
+                distance_to_arrival = $7,
+                landable = $8,
+                gravity = $9,
+                terraform_state = $10,
+                atmosphere = $11,
+                updated_at = $12
+            WHERE bodies.updated_at < $12
+            RETURNING id
+            "#,
+            body.system_address as i64,
+            body.id as i16,
+            body.name,
+            body.ty as _,
+            body.star_class,
+            body.planet_class,
+            body.distance_to_arrival,
+            body.landable,
+            body.gravity,
+            body.terraform_state,
+            body.atmosphere,
+            body.updated_at.naive_utc())
+            .fetch_optional(&db.pool)
+            .await?;
+
+        if row.is_some() {
+            sqlx::query!(
+                r#"
+                DELETE FROM body_rings
+                WHERE system_address = $1 AND body_id = $2
+                "#,
+                body.system_address as i64,
+                body.id as i16)
+                .execute(&db.pool)
+                .await?;
+
+            for ring in &body.rings {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO body_rings
+                        (system_address,
+                         body_id,
+                         name,
+                         class,
+                         inner_radius,
+                         outer_radius,
+                         mass)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7)
+                    "#,
+                    body.system_address as i64,
+                    body.id as i16,
+                    ring.name,
+                    ring.class,
+                    ring.inner_radius,
+                    ring.outer_radius,
+                    ring.mass)
+                    .execute(&db.pool)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn from_journal(db: &Database, scan: &Scan, timestamp: DateTime<Utc>)
+        -> Result<Self, Error>
+    {
+        let ty = if scan.star_type.is_some() {
+            BodyType::Star
+        } else if scan.planet_class.is_some() {
+            BodyType::Planet
+        } else {
+            BodyType::Belt
+        };
+
+        let body = Body {
+            system_address: scan.system_address,
+            id: scan.body_id,
+            name: scan.body_name.clone(),
+            ty,
+            star_class: scan.star_type.clone(),
+            planet_class: scan.planet_class.clone(),
+            distance_to_arrival: scan.distance_from_arrival_ls,
+            landable: scan.landable,
+            gravity: scan.surface_gravity.map(|g| g / G),
+            terraform_state: scan.terraform_state.clone().filter(|s| !s.is_empty()),
+            atmosphere: scan.atmosphere.clone().filter(|s| !s.is_empty()),
+            rings: scan.rings.iter().map(|ring| {
+                Ring {
+                    name: ring.name.clone(),
+                    class: ring.ring_class.clone(),
+                    inner_radius: ring.inner_rad,
+                    outer_radius: ring.outer_rad,
+                    mass: ring.mass_mt,
+                }
+            }).collect(),
+            updated_at: timestamp,
+        };
+
+        Body::create(db, &body).await?;
+
+        // The arrival star is the system's primary star.
+        if let (BodyType::Star, Some(star_class)) = (body.ty, &body.star_class) {
+            if body.distance_to_arrival == 0. {
+                System::set_primary_star_class(db, body.system_address, star_class).await?;
+            }
+        }
+
+        Ok(body)
+    }
+
+    pub async fn fetch_all(db: &Database, system_address: u64) -> Result<Vec<Self>, Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                system_address,
+                id,
+                name,
+                type AS "ty: BodyType",
+                star_class,
+                planet_class,
+                distance_to_arrival,
+                landable,
+                gravity,
+                terraform_state,
+                atmosphere,
+                updated_at
+            FROM bodies
+            WHERE system_address = $1
+            ORDER BY distance_to_arrival, id
+            "#, system_address as i64)
+            .fetch_all(&db.pool)
+            .await?;
+
+        let rings = sqlx::query!(
+            r#"
+            SELECT
+                body_id,
+                name,
+                class,
+                inner_radius,
+                outer_radius,
+                mass
+            FROM body_rings
+            WHERE system_address = $1
+            ORDER BY inner_radius
+            "#, system_address as i64)
+            .fetch_all(&db.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| {
+            Body {
+                system_address: row.system_address as u64,
+                id: row.id as u16,
+                name: row.name,
+                ty: row.ty,
+                star_class: row.star_class,
+                planet_class: row.planet_class,
+                distance_to_arrival: row.distance_to_arrival,
+                landable: row.landable,
+                gravity: row.gravity,
+                terraform_state: row.terraform_state,
+                atmosphere: row.atmosphere,
+                rings: rings.iter().filter(|r| r.body_id == row.id).map(|r| {
+                    Ring {
+                        name: r.name.clone(),
+                        class: r.class.clone(),
+                        inner_radius: r.inner_radius,
+                        outer_radius: r.outer_radius,
+                        mass: r.mass,
+                    }
+                }).collect(),
+                updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
+            }
+        }).collect())
+    }
+}
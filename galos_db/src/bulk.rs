@@ -0,0 +1,1026 @@
+//! Batched imports, for loading whole dumps.
+//!
+//! Rows are buffered in a `Batch`, then on `flush` they are `COPY`ed into temporary staging
+//! tables and merged into `systems`, `system_names`, `factions`, `system_factions` and
+//! `system_faction_states` with one statement per table. The same `updated_at` precedence as
+//! `System::create` and `SystemFaction::create` applies: a row only replaces an older one.
+//! Systems which collide with another's position are logged in `system_collisions`, with their
+//! factions.
+//!
+//! Bodies, stations, and the market and outfitting snapshots of stations are merged the same
+//! way, like `Body::create`, `Station::create`, `MarketPrice::snapshot` and the `Outfitting`
+//! snapshots.
+
+use std::fmt::Display;
+use chrono::{DateTime, Utc};
+use sqlx::Executor;
+use elite_journal::{prelude::*, faction::State as JournalState, system::Coordinate};
+use crate::{Error, Database};
+use crate::bodies::Body;
+use crate::markets::MarketPrice;
+use crate::stations::Station;
+
+/// A system, as given to `System::create`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemRow {
+    pub address: u64,
+    pub name: String,
+    pub position: Coordinate,
+    pub population: Option<u64>,
+    pub security: Option<Security>,
+    pub government: Option<Government>,
+    pub allegiance: Option<Allegiance>,
+    pub primary_economy: Option<Economy>,
+    pub secondary_economy: Option<Economy>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A faction's presence in a system, as given to `SystemFaction::create`. The faction is
+/// created by name when it doesn't exist.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemFactionRow {
+    pub system_address: u64,
+    pub faction: String,
+    pub state: Option<JournalState>,
+    pub influence: f32,
+    pub happiness: Option<Happiness>,
+    pub government: Government,
+    pub allegiance: Allegiance,
+    /// The faction's pending, active and recovering states, which replace those stored. `None`
+    /// when the source doesn't list them, which keeps the stored states.
+    pub states: Option<Vec<(JournalState, Status)>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Every price at a market, as given to `MarketPrice::snapshot`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketRow {
+    pub market_id: u64,
+    pub prices: Vec<MarketPrice>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// The modules or ships sold at a station, as given to `Outfitting::snapshot_modules` and
+/// `Outfitting::snapshot_ships`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutfittingRow {
+    pub market_id: u64,
+    pub items: Vec<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// The number of rows written by a `Batch::flush`, ignoring rows which were older than what
+/// was already stored.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    pub systems: u64,
+    pub system_factions: u64,
+    pub bodies: u64,
+    pub stations: u64,
+    /// Market, outfitting and shipyard snapshots.
+    pub snapshots: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct Batch {
+    systems: Vec<SystemRow>,
+    system_factions: Vec<SystemFactionRow>,
+    bodies: Vec<Body>,
+    stations: Vec<Station>,
+    markets: Vec<MarketRow>,
+    modules: Vec<OutfittingRow>,
+    ships: Vec<OutfittingRow>,
+}
+
+impl Batch {
+    pub fn new() -> Self {
+        Batch::default()
+    }
+
+    pub fn add_system(&mut self, row: SystemRow) {
+        self.systems.push(row);
+    }
+
+    /// Factions are merged after systems, so the system may be added in the same batch.
+    pub fn add_system_faction(&mut self, row: SystemFactionRow) {
+        self.system_factions.push(row);
+    }
+
+    /// Bodies don't need their system to be stored, and neither do stations.
+    pub fn add_body(&mut self, body: Body) {
+        self.bodies.push(body);
+    }
+
+    pub fn add_station(&mut self, station: Station) {
+        self.stations.push(station);
+    }
+
+    pub fn add_market(&mut self, row: MarketRow) {
+        self.markets.push(row);
+    }
+
+    pub fn add_modules(&mut self, row: OutfittingRow) {
+        self.modules.push(row);
+    }
+
+    pub fn add_ships(&mut self, row: OutfittingRow) {
+        self.ships.push(row);
+    }
+
+    /// The number of buffered rows, counting each snapshot as one.
+    pub fn len(&self) -> usize {
+        self.systems.len() + self.system_factions.len() + self.bodies.len() +
+            self.stations.len() + self.markets.len() + self.modules.len() + self.ships.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Writes every buffered row in a single transaction, and clears the batch.
+    pub async fn flush(&mut self, db: &Database) -> Result<Stats, Error> {
+        let mut stats = Stats::default();
+        if self.is_empty() {
+            return Ok(stats);
+        }
+
+        let mut tx = db.pool.begin().await?;
+
+        // Several statements, so this can't be a prepared query.
+        (&mut tx).execute(
+            r#"
+            CREATE TEMPORARY TABLE staging_systems (
+                address            bigint,
+                name               varchar,
+                x                  double precision,
+                y                  double precision,
+                z                  double precision,
+                population         bigint,
+                security           Security,
+                government         Government,
+                allegiance         Allegiance,
+                primary_economy    Economy,
+                secondary_economy  Economy,
+                updated_at         timestamp
+            ) ON COMMIT DROP;
+
+            CREATE TEMPORARY TABLE staging_system_factions (
+                system_address  bigint,
+                faction         varchar,
+                state           State,
+                influence       real,
+                happiness       Happiness,
+                government      Government,
+                allegiance      Allegiance,
+                updated_at      timestamp,
+                states_known    boolean,
+                written         boolean  NOT NULL DEFAULT false
+            ) ON COMMIT DROP;
+
+            CREATE TEMPORARY TABLE staging_system_faction_states (
+                system_address  bigint,
+                faction         varchar,
+                state           State,
+                status          Status
+            ) ON COMMIT DROP;
+
+            CREATE TEMPORARY TABLE staging_bodies (
+                system_address       bigint,
+                id                   smallint,
+                name                 varchar,
+                type                 BodyType,
+                star_class           varchar,
+                planet_class         varchar,
+                distance_to_arrival  double precision,
+                landable             boolean,
+                gravity              double precision,
+                terraform_state      varchar,
+                atmosphere           varchar,
+                updated_at           timestamp,
+                written              boolean  NOT NULL DEFAULT false
+            ) ON COMMIT DROP;
+
+            CREATE TEMPORARY TABLE staging_body_rings (
+                system_address  bigint,
+                body_id         smallint,
+                name            varchar,
+                class           varchar,
+                inner_radius    double precision,
+                outer_radius    double precision,
+                mass            double precision,
+                updated_at      timestamp
+            ) ON COMMIT DROP;
+
+            CREATE TEMPORARY TABLE staging_stations (
+                market_id            bigint,
+                system_address       bigint,
+                name                 varchar,
+                type                 varchar,
+                distance_to_arrival  double precision,
+                landing_pad          LandingPad,
+                services             text[],
+                faction              varchar,
+                government           Government,
+                allegiance           Allegiance,
+                primary_economy      Economy,
+                secondary_economy    Economy,
+                updated_at           timestamp
+            ) ON COMMIT DROP;
+
+            CREATE TEMPORARY TABLE staging_markets (
+                market_id   bigint,
+                updated_at  timestamp,
+                written     boolean  NOT NULL DEFAULT false
+            ) ON COMMIT DROP;
+
+            CREATE TEMPORARY TABLE staging_market_prices (
+                market_id   bigint,
+                commodity   varchar,
+                buy_price   integer,
+                sell_price  integer,
+                stock       integer,
+                demand      integer,
+                updated_at  timestamp
+            ) ON COMMIT DROP;
+
+            CREATE TEMPORARY TABLE staging_module_snapshots (
+                market_id   bigint,
+                updated_at  timestamp,
+                written     boolean  NOT NULL DEFAULT false
+            ) ON COMMIT DROP;
+
+            CREATE TEMPORARY TABLE staging_station_modules (
+                market_id   bigint,
+                module      varchar,
+                updated_at  timestamp
+            ) ON COMMIT DROP;
+
+            CREATE TEMPORARY TABLE staging_ship_snapshots (
+                market_id   bigint,
+                updated_at  timestamp,
+                written     boolean  NOT NULL DEFAULT false
+            ) ON COMMIT DROP;
+
+            CREATE TEMPORARY TABLE staging_station_ships (
+                market_id   bigint,
+                ship        varchar,
+                updated_at  timestamp
+            ) ON COMMIT DROP;
+            "#)
+            .await?;
+
+        let mut systems = String::new();
+        for s in self.systems.drain(..) {
+            copy_row(&mut systems, &[
+                Some(s.address as i64).map(text),
+                Some(s.name.to_uppercase()),
+                Some(s.position.x).map(text),
+                Some(s.position.y).map(text),
+                Some(s.position.z).map(text),
+                s.population.map(text),
+                s.security.map(label),
+                s.government.map(label),
+                s.allegiance.map(label),
+                s.primary_economy.map(label),
+                s.secondary_economy.map(label),
+                Some(s.updated_at.naive_utc()).map(text),
+            ]);
+        }
+
+        let mut factions = String::new();
+        let mut states = String::new();
+        for f in self.system_factions.drain(..) {
+            copy_row(&mut factions, &[
+                Some(f.system_address as i64).map(text),
+                Some(f.faction.clone()),
+                f.state.map(label),
+                Some(f.influence).map(text),
+                f.happiness.map(label),
+                Some(f.government).map(label),
+                Some(f.allegiance).map(label),
+                Some(f.updated_at.naive_utc()).map(text),
+                Some(if f.states.is_some() { "t" } else { "f" }.to_string()),
+            ]);
+            for (state, status) in f.states.into_iter().flatten() {
+                copy_row(&mut states, &[
+                    Some(f.system_address as i64).map(text),
+                    Some(f.faction.clone()),
+                    Some(state).map(label),
+                    Some(status).map(label),
+                ]);
+            }
+        }
+
+        copy_in(&mut tx, "COPY staging_systems FROM STDIN", systems).await?;
+        copy_in(&mut tx,
+            "COPY staging_system_factions (system_address, faction, state, influence, happiness, government, allegiance, updated_at, states_known) FROM STDIN",
+            factions).await?;
+        copy_in(&mut tx, "COPY staging_system_faction_states FROM STDIN", states).await?;
+
+        let mut bodies = String::new();
+        let mut rings = String::new();
+        for b in self.bodies.drain(..) {
+            copy_row(&mut bodies, &[
+                Some(b.system_address as i64).map(text),
+                Some(b.id as i16).map(text),
+                Some(b.name),
+                Some(b.ty).map(label),
+                b.star_class,
+                b.planet_class,
+                Some(b.distance_to_arrival).map(text),
+                b.landable.map(text),
+                b.gravity.map(text),
+                b.terraform_state,
+                b.atmosphere,
+                Some(b.updated_at.naive_utc()).map(text),
+            ]);
+            for ring in b.rings {
+                copy_row(&mut rings, &[
+                    Some(b.system_address as i64).map(text),
+                    Some(b.id as i16).map(text),
+                    Some(ring.name),
+                    Some(ring.class),
+                    Some(ring.inner_radius).map(text),
+                    Some(ring.outer_radius).map(text),
+                    Some(ring.mass).map(text),
+                    Some(b.updated_at.naive_utc()).map(text),
+                ]);
+            }
+        }
+        copy_in(&mut tx,
+            "COPY staging_bodies (system_address, id, name, type, star_class, planet_class, distance_to_arrival, landable, gravity, terraform_state, atmosphere, updated_at) FROM STDIN",
+            bodies).await?;
+        copy_in(&mut tx, "COPY staging_body_rings FROM STDIN", rings).await?;
+
+        let mut stations = String::new();
+        for s in self.stations.drain(..) {
+            copy_row(&mut stations, &[
+                Some(s.market_id as i64).map(text),
+                Some(s.system_address as i64).map(text),
+                Some(s.name),
+                s.ty,
+                s.distance_to_arrival.map(text),
+                s.landing_pad.map(label),
+                Some(array(&s.services)),
+                s.faction,
+                s.government.map(label),
+                s.allegiance.map(label),
+                s.primary_economy.map(label),
+                s.secondary_economy.map(label),
+                Some(s.updated_at.naive_utc()).map(text),
+            ]);
+        }
+        copy_in(&mut tx, "COPY staging_stations FROM STDIN", stations).await?;
+
+        let mut markets = String::new();
+        let mut prices = String::new();
+        for m in self.markets.drain(..) {
+            copy_row(&mut markets, &[
+                Some(m.market_id as i64).map(text),
+                Some(m.updated_at.naive_utc()).map(text),
+            ]);
+            for p in m.prices {
+                copy_row(&mut prices, &[
+                    Some(m.market_id as i64).map(text),
+                    Some(p.commodity.to_lowercase()),
+                    Some(p.buy_price as i32).map(text),
+                    Some(p.sell_price as i32).map(text),
+                    Some(p.stock as i32).map(text),
+                    Some(p.demand as i32).map(text),
+                    Some(m.updated_at.naive_utc()).map(text),
+                ]);
+            }
+        }
+        copy_in(&mut tx, "COPY staging_markets (market_id, updated_at) FROM STDIN", markets).await?;
+        copy_in(&mut tx, "COPY staging_market_prices FROM STDIN", prices).await?;
+
+        for (rows, snapshots, items) in vec![
+            (self.modules.drain(..), "staging_module_snapshots", "staging_station_modules"),
+            (self.ships.drain(..), "staging_ship_snapshots", "staging_station_ships"),
+        ] {
+            let mut snapshot_rows = String::new();
+            let mut item_rows = String::new();
+            for o in rows {
+                copy_row(&mut snapshot_rows, &[
+                    Some(o.market_id as i64).map(text),
+                    Some(o.updated_at.naive_utc()).map(text),
+                ]);
+                for item in o.items {
+                    copy_row(&mut item_rows, &[
+                        Some(o.market_id as i64).map(text),
+                        Some(item.to_lowercase()),
+                        Some(o.updated_at.naive_utc()).map(text),
+                    ]);
+                }
+            }
+            copy_in(&mut tx, &format!("COPY {} (market_id, updated_at) FROM STDIN", snapshots),
+                snapshot_rows).await?;
+            copy_in(&mut tx, &format!("COPY {} FROM STDIN", items), item_rows).await?;
+        }
+
+        // Only the newest row for each system is kept, and systems positioned on top of
+        // another system are skipped, rather than failing the whole batch.
+        stats.systems = sqlx::query(
+            r#"
+            INSERT INTO systems
+                (address,
+                 name,
+                 position,
+                 population,
+                 security,
+                 government,
+                 allegiance,
+                 primary_economy,
+                 secondary_economy,
+                 updated_at)
+            SELECT
+                address,
+                name,
+                ST_MakePoint(x, y, z),
+                population,
+                security,
+                government,
+                allegiance,
+                primary_economy,
+                secondary_economy,
+                updated_at
+            FROM (
+                SELECT DISTINCT ON (x, y, z) *
+                FROM (
+                    SELECT DISTINCT ON (address) *
+                    FROM staging_systems
+                    ORDER BY address, updated_at DESC
+                ) newest
+                ORDER BY x, y, z, updated_at DESC
+            ) s
+            WHERE NOT EXISTS (
+                SELECT 1 FROM systems o
+                WHERE o.position = ST_MakePoint(s.x, s.y, s.z)
+                  AND o.address <> s.address
+            )
+            ON CONFLICT (address)
+            DO UPDATE SET
+                population = EXCLUDED.population,
+                security = EXCLUDED.security,
+                government = EXCLUDED.government,
+                allegiance = EXCLUDED.allegiance,
+                primary_economy = EXCLUDED.primary_economy,
+                secondary_economy = EXCLUDED.secondary_economy,
+                updated_at = EXCLUDED.updated_at
+            WHERE systems.updated_at < EXCLUDED.updated_at
+            "#)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+
+        // Systems positioned on top of another system are logged, like `Collision::detect`. This
+        // runs after the merge, so it also catches systems which lost out to another system at
+        // the same position in this batch.
+        sqlx::query(
+            r#"
+            INSERT INTO system_collisions
+                (address, name, position, existing_address, first_seen_at, last_seen_at)
+            SELECT DISTINCT ON (s.address, o.address)
+                s.address,
+                s.name,
+                ST_MakePoint(s.x, s.y, s.z),
+                o.address,
+                s.updated_at,
+                s.updated_at
+            FROM staging_systems s
+            JOIN systems o
+              ON o.position = ST_MakePoint(s.x, s.y, s.z)
+             AND o.address <> s.address
+            ORDER BY s.address, o.address, s.updated_at DESC
+            ON CONFLICT (address, existing_address)
+            DO UPDATE SET
+                name = EXCLUDED.name,
+                first_seen_at = LEAST(system_collisions.first_seen_at, EXCLUDED.first_seen_at),
+                last_seen_at = GREATEST(system_collisions.last_seen_at, EXCLUDED.last_seen_at),
+                count = system_collisions.count + 1
+            "#)
+            .execute(&mut tx)
+            .await?;
+
+        // New systems get their name from a trigger, this records names given to systems which
+        // were already known by another.
+        sqlx::query(
+            r#"
+            INSERT INTO system_names (address, name, kind)
+            SELECT DISTINCT
+                s.address,
+                s.name,
+                CASE WHEN systems.name = s.name
+                    THEN system_name_kind(s.name)
+                    ELSE 'Renamed'
+                END
+            FROM staging_systems s
+            JOIN systems USING (address)
+            ON CONFLICT DO NOTHING
+            "#)
+            .execute(&mut tx)
+            .await?;
+
+        // Including the factions controlling stations, like `Station::create`.
+        sqlx::query(
+            r#"
+            INSERT INTO factions (name)
+            SELECT DISTINCT ON (lower(faction)) faction
+            FROM (
+                SELECT faction FROM staging_system_factions
+                UNION ALL
+                SELECT faction FROM staging_stations WHERE faction IS NOT NULL
+            ) f
+            ON CONFLICT (lower(name)) DO NOTHING
+            "#)
+            .execute(&mut tx)
+            .await?;
+
+        // Factions in systems which weren't stored because they collided are kept with the
+        // collision, like `Collision::add_faction`.
+        sqlx::query(
+            r#"
+            INSERT INTO system_collision_factions
+                (address,
+                 existing_address,
+                 faction_id,
+                 state,
+                 influence,
+                 happiness,
+                 government,
+                 allegiance,
+                 updated_at)
+            SELECT DISTINCT ON (s.system_address, c.existing_address, f.id)
+                s.system_address,
+                c.existing_address,
+                f.id,
+                s.state,
+                s.influence,
+                s.happiness,
+                s.government,
+                s.allegiance,
+                s.updated_at
+            FROM staging_system_factions s
+            JOIN factions f ON lower(f.name) = lower(s.faction)
+            JOIN system_collisions c ON c.address = s.system_address
+            WHERE NOT EXISTS (SELECT 1 FROM systems WHERE address = s.system_address)
+            ORDER BY s.system_address, c.existing_address, f.id, s.updated_at DESC
+            ON CONFLICT (address, existing_address, faction_id)
+            DO UPDATE SET
+                state = EXCLUDED.state,
+                influence = EXCLUDED.influence,
+                happiness = EXCLUDED.happiness,
+                government = EXCLUDED.government,
+                allegiance = EXCLUDED.allegiance,
+                updated_at = EXCLUDED.updated_at
+            WHERE system_collision_factions.updated_at < EXCLUDED.updated_at
+            "#)
+            .execute(&mut tx)
+            .await?;
+
+        // Rows for other systems which don't exist are dropped, rather than failing the batch.
+        stats.system_factions = sqlx::query(
+            r#"
+            WITH written AS (
+                INSERT INTO system_factions
+                    (system_address,
+                     faction_id,
+                     state,
+                     influence,
+                     happiness,
+                     government,
+                     allegiance,
+                     updated_at)
+                SELECT DISTINCT ON (s.system_address, f.id)
+                    s.system_address,
+                    f.id,
+                    s.state,
+                    s.influence,
+                    s.happiness,
+                    s.government,
+                    s.allegiance,
+                    s.updated_at
+                FROM staging_system_factions s
+                JOIN factions f ON lower(f.name) = lower(s.faction)
+                JOIN systems ON systems.address = s.system_address
+                ORDER BY s.system_address, f.id, s.updated_at DESC
+                ON CONFLICT (system_address, faction_id)
+                DO UPDATE SET
+                    state = EXCLUDED.state,
+                    influence = EXCLUDED.influence,
+                    happiness = EXCLUDED.happiness,
+                    government = EXCLUDED.government,
+                    allegiance = EXCLUDED.allegiance,
+                    updated_at = EXCLUDED.updated_at
+                WHERE system_factions.updated_at < EXCLUDED.updated_at
+                RETURNING system_address, faction_id, updated_at
+            )
+            UPDATE staging_system_factions s
+            SET written = true
+            FROM written
+            JOIN factions f ON f.id = written.faction_id
+            WHERE s.system_address = written.system_address
+              AND lower(s.faction) = lower(f.name)
+              AND s.updated_at = written.updated_at
+            "#)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+
+        // States are replaced for every presence which was written with its states, like
+        // `SystemFaction::create`.
+        sqlx::query(
+            r#"
+            DELETE FROM system_faction_states st
+            USING staging_system_factions s, factions f
+            WHERE s.written
+              AND s.states_known
+              AND lower(f.name) = lower(s.faction)
+              AND st.system_address = s.system_address
+              AND st.faction_id = f.id
+            "#)
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO system_faction_states (system_address, faction_id, state, status)
+            SELECT DISTINCT st.system_address, f.id, st.state, st.status
+            FROM staging_system_faction_states st
+            JOIN staging_system_factions s
+              ON s.system_address = st.system_address
+             AND lower(s.faction) = lower(st.faction)
+             AND s.written
+            JOIN factions f ON lower(f.name) = lower(st.faction)
+            "#)
+            .execute(&mut tx)
+            .await?;
+
+        stats.bodies = sqlx::query(
+            r#"
+            WITH written AS (
+                INSERT INTO bodies
+                    (system_address,
+                     id,
+                     name,
+                     type,
+                     star_class,
+                     planet_class,
+                     distance_to_arrival,
+                     landable,
+                     gravity,
+                     terraform_state,
+                     atmosphere,
+                     updated_at)
+                SELECT DISTINCT ON (system_address, id)
+                    system_address,
+                    id,
+                    name,
+                    type,
+                    star_class,
+                    planet_class,
+                    distance_to_arrival,
+                    landable,
+                    gravity,
+                    terraform_state,
+                    atmosphere,
+                    updated_at
+                FROM staging_bodies
+                ORDER BY system_address, id, updated_at DESC
+                ON CONFLICT (system_address, id)
+                DO UPDATE SET
+                    name = EXCLUDED.name,
+                    type = EXCLUDED.type,
+                    star_class = EXCLUDED.star_class,
+                    planet_class = EXCLUDED.planet_class,
+                    distance_to_arrival = EXCLUDED.distance_to_arrival,
+                    landable = EXCLUDED.landable,
+                    gravity = EXCLUDED.gravity,
+                    terraform_state = EXCLUDED.terraform_state,
+                    atmosphere = EXCLUDED.atmosphere,
+                    updated_at = EXCLUDED.updated_at
+                WHERE bodies.updated_at < EXCLUDED.updated_at
+                RETURNING system_address, id, updated_at
+            )
+            UPDATE staging_bodies s
+            SET written = true
+            FROM written
+            WHERE s.system_address = written.system_address
+              AND s.id = written.id
+              AND s.updated_at = written.updated_at
+            "#)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+
+        // Rings are replaced for every body which was written.
+        sqlx::query(
+            r#"
+            DELETE FROM body_rings r
+            USING staging_bodies s
+            WHERE s.written
+              AND r.system_address = s.system_address
+              AND r.body_id = s.id
+            "#)
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO body_rings
+                (system_address, body_id, name, class, inner_radius, outer_radius, mass)
+            SELECT DISTINCT ON (r.system_address, r.body_id, r.name)
+                r.system_address,
+                r.body_id,
+                r.name,
+                r.class,
+                r.inner_radius,
+                r.outer_radius,
+                r.mass
+            FROM staging_body_rings r
+            JOIN staging_bodies s
+              ON s.system_address = r.system_address
+             AND s.id = r.body_id
+             AND s.updated_at = r.updated_at
+             AND s.written
+            "#)
+            .execute(&mut tx)
+            .await?;
+
+        // The arrival star is the system's primary star, like `Body::from_journal`.
+        sqlx::query(
+            r#"
+            UPDATE systems
+            SET primary_star_class = s.star_class
+            FROM staging_bodies s
+            WHERE s.written
+              AND s.type = 'Star'
+              AND s.star_class IS NOT NULL
+              AND s.distance_to_arrival = 0
+              AND systems.address = s.system_address
+            "#)
+            .execute(&mut tx)
+            .await?;
+
+        stats.stations = sqlx::query(
+            r#"
+            INSERT INTO stations
+                (market_id,
+                 system_address,
+                 name,
+                 type,
+                 distance_to_arrival,
+                 landing_pad,
+                 services,
+                 faction_id,
+                 government,
+                 allegiance,
+                 primary_economy,
+                 secondary_economy,
+                 updated_at)
+            SELECT DISTINCT ON (s.market_id)
+                s.market_id,
+                s.system_address,
+                s.name,
+                s.type,
+                s.distance_to_arrival,
+                s.landing_pad,
+                s.services,
+                f.id,
+                s.government,
+                s.allegiance,
+                s.primary_economy,
+                s.secondary_economy,
+                s.updated_at
+            FROM staging_stations s
+            LEFT JOIN factions f ON lower(f.name) = lower(s.faction)
+            ORDER BY s.market_id, s.updated_at DESC
+            ON CONFLICT (market_id)
+            DO UPDATE SET
+                system_address = EXCLUDED.system_address,
+                name = EXCLUDED.name,
+                type = COALESCE(EXCLUDED.type, stations.type),
+                distance_to_arrival = COALESCE(EXCLUDED.distance_to_arrival, stations.distance_to_arrival),
+                landing_pad = COALESCE(EXCLUDED.landing_pad, stations.landing_pad),
+                services = EXCLUDED.services,
+                faction_id = COALESCE(EXCLUDED.faction_id, stations.faction_id),
+                government = COALESCE(EXCLUDED.government, stations.government),
+                allegiance = COALESCE(EXCLUDED.allegiance, stations.allegiance),
+                primary_economy = COALESCE(EXCLUDED.primary_economy, stations.primary_economy),
+                secondary_economy = COALESCE(EXCLUDED.secondary_economy, stations.secondary_economy),
+                updated_at = EXCLUDED.updated_at
+            WHERE stations.updated_at < EXCLUDED.updated_at
+            "#)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+
+        stats.snapshots += replace_snapshots(&mut tx, "staging_markets", "market_prices").await?;
+        sqlx::query(
+            r#"
+            INSERT INTO market_prices
+                (market_id, commodity, buy_price, sell_price, stock, demand, updated_at)
+            SELECT DISTINCT ON (p.market_id, p.commodity)
+                p.market_id,
+                p.commodity,
+                p.buy_price,
+                p.sell_price,
+                p.stock,
+                p.demand,
+                p.updated_at
+            FROM staging_market_prices p
+            JOIN staging_markets s
+              ON s.market_id = p.market_id
+             AND s.updated_at = p.updated_at
+             AND s.written
+            "#)
+            .execute(&mut tx)
+            .await?;
+
+        stats.snapshots += replace_snapshots(&mut tx, "staging_module_snapshots", "station_modules").await?;
+        sqlx::query(
+            r#"
+            INSERT INTO station_modules (market_id, module, updated_at)
+            SELECT DISTINCT m.market_id, m.module, m.updated_at
+            FROM staging_station_modules m
+            JOIN staging_module_snapshots s
+              ON s.market_id = m.market_id
+             AND s.updated_at = m.updated_at
+             AND s.written
+            "#)
+            .execute(&mut tx)
+            .await?;
+
+        stats.snapshots += replace_snapshots(&mut tx, "staging_ship_snapshots", "station_ships").await?;
+        sqlx::query(
+            r#"
+            INSERT INTO station_ships (market_id, ship, updated_at)
+            SELECT DISTINCT sh.market_id, sh.ship, sh.updated_at
+            FROM staging_station_ships sh
+            JOIN staging_ship_snapshots s
+              ON s.market_id = sh.market_id
+             AND s.updated_at = sh.updated_at
+             AND s.written
+            "#)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(stats)
+    }
+}
+
+async fn copy_in(tx: &mut sqlx::Transaction<'_, sqlx::Postgres>, statement: &str, data: String)
+    -> Result<(), Error>
+{
+    if data.is_empty() {
+        return Ok(());
+    }
+
+    let mut copy = tx.copy_in_raw(statement).await?;
+    copy.send(data.into_bytes()).await?;
+    copy.finish().await?;
+    Ok(())
+}
+
+// Marks the newest snapshot of each market in the staging table `snapshots` which is newer than
+// what's stored in `table`, and deletes the stored rows it replaces.
+async fn replace_snapshots(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    snapshots: &str,
+    table: &str)
+    -> Result<u64, Error>
+{
+    let written = sqlx::query(&format!(
+        r#"
+        UPDATE {snapshots} s
+        SET written = true
+        FROM (
+            SELECT DISTINCT ON (market_id) market_id, updated_at
+            FROM {snapshots}
+            ORDER BY market_id, updated_at DESC
+        ) newest
+        WHERE s.market_id = newest.market_id
+          AND s.updated_at = newest.updated_at
+          AND NOT EXISTS (
+              SELECT 1 FROM {table} t
+              WHERE t.market_id = s.market_id
+                AND t.updated_at >= s.updated_at
+          )
+        "#, snapshots = snapshots, table = table))
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+    sqlx::query(&format!(
+        r#"
+        DELETE FROM {table} t
+        USING {snapshots} s
+        WHERE s.written
+          AND t.market_id = s.market_id
+        "#, snapshots = snapshots, table = table))
+        .execute(&mut *tx)
+        .await?;
+
+    Ok(written)
+}
+
+fn text<T: Display>(value: T) -> String {
+    value.to_string()
+}
+
+// Enum labels match their variant names.
+fn label<T: std::fmt::Debug>(value: T) -> String {
+    format!("{:?}", value)
+}
+
+// An array literal, like `{"Dock","Refuel"}`.
+fn array(values: &[String]) -> String {
+    let elements: Vec<String> = values.iter().map(|v| {
+        format!("\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\""))
+    }).collect();
+    format!("{{{}}}", elements.join(","))
+}
+
+// Appends a row in `COPY`'s text format.
+fn copy_row(buf: &mut String, fields: &[Option<String>]) {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            buf.push('\t');
+        }
+        match field {
+            Some(field) => {
+                for c in field.chars() {
+                    match c {
+                        '\\' => buf.push_str("\\\\"),
+                        '\t' => buf.push_str("\\t"),
+                        '\n' => buf.push_str("\\n"),
+                        '\r' => buf.push_str("\\r"),
+                        c => buf.push(c),
+                    }
+                }
+            },
+            None => buf.push_str("\\N"),
+        }
+    }
+    buf.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use crate::{systems::System, factions::{Faction, SystemFaction}};
+    use super::*;
+
+    fn system_faction(address: u64, faction: &str, states: Option<Vec<(JournalState, Status)>>)
+        -> SystemFactionRow
+    {
+        SystemFactionRow {
+            system_address: address,
+            faction: faction.into(),
+            state: Some(JournalState::Boom),
+            influence: 0.5,
+            happiness: None,
+            government: Government::Democracy,
+            allegiance: Allegiance::Federation,
+            states,
+            updated_at: Utc::now(),
+        }
+    }
+
+    // Needs a migrated database, given by DATABASE_URL.
+    #[async_std::test]
+    #[ignore]
+    async fn flush_keeps_unknown_states() {
+        let db = Database::new().await.unwrap();
+        let id = std::process::id();
+        let address = 1 << 40 | id as u64;
+        let name = format!("Galos Bulk Test {}", id);
+        let position = Coordinate { x: -1e5, y: -1e5, z: id as f64 };
+        let earlier = Utc::now() - Duration::days(1);
+        System::create(&db, address, &name, position,
+            None, None, None, None, None, None, earlier).await.unwrap();
+        let faction = Faction::create(&db, &name).await.unwrap();
+        SystemFaction::create(&db, address, faction.id as u32, Some(JournalState::Boom), 0.5,
+            None, Government::Democracy, Allegiance::Federation,
+            &[(JournalState::Boom, Status::Active)], earlier).await.unwrap();
+
+        // Like a Spansh dump, which doesn't list states.
+        let mut batch = Batch::new();
+        batch.add_system_faction(system_faction(address, &name, None));
+        assert_eq!(batch.flush(&db).await.unwrap().system_factions, 1);
+        let timeline = SystemFaction::state_timeline(&db, Some(address), faction.id as u32)
+            .await.unwrap();
+        assert_eq!(timeline.len(), 1);
+        assert_eq!((&timeline[0].state, &timeline[0].status, timeline[0].ended_at),
+            (&JournalState::Boom, &Status::Active, None));
+
+        // Like an EDSM dump, which lists them all.
+        batch.add_system_faction(system_faction(address, &name, Some(vec![])));
+        assert_eq!(batch.flush(&db).await.unwrap().system_factions, 1);
+        let timeline = SystemFaction::state_timeline(&db, Some(address), faction.id as u32)
+            .await.unwrap();
+        assert_eq!(timeline.len(), 1);
+        assert!(timeline[0].ended_at.is_some());
+    }
+}
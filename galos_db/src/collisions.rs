@@ -0,0 +1,193 @@
+//! Systems which share a position with a different system.
+//!
+//! `systems.position` is unique, and two addresses at one position is either a renamed or
+//! re-addressed system, or bad data. We can't tell which, so the existing system is kept as it
+//! is, the new one is logged here for review, and the write it came from carries on without it.
+//! The factions reported in the new system are kept with it.
+
+use chrono::{DateTime, Utc};
+use geozero::wkb;
+use elite_journal::{prelude::*, faction::State as JournalState, system::Coordinate};
+use crate::{Error, Database};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Collision {
+    pub address: i64,
+    pub name: String,
+    pub position: Coordinate,
+    pub existing_address: i64,
+    pub existing_name: String,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    /// The number of times the colliding system was seen.
+    pub count: i32,
+}
+
+/// A faction reported in a colliding system, see `Collision::add_faction`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollisionFaction {
+    pub address: i64,
+    pub existing_address: i64,
+    pub faction: String,
+    pub state: Option<JournalState>,
+    pub influence: f32,
+    pub happiness: Option<Happiness>,
+    pub government: Government,
+    pub allegiance: Allegiance,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Collision {
+    /// Logs the system when another system is already at its position, returning whether it
+    /// was.
+    pub async fn detect(db: &Database,
+        address: u64,
+        name: &str,
+        position: Coordinate,
+        seen_at: DateTime<Utc>)
+        -> Result<bool, Error>
+    {
+        let row = sqlx::query!(
+            r#"
+            INSERT INTO system_collisions
+                (address, name, position, existing_address, first_seen_at, last_seen_at)
+            SELECT $1, UPPER($2), $3::geometry, address, $4, $4
+            FROM systems
+            WHERE position = $3::geometry AND address <> $1
+            ON CONFLICT (address, existing_address)
+            DO UPDATE SET
+                name = EXCLUDED.name,
+                first_seen_at = LEAST(system_collisions.first_seen_at, EXCLUDED.first_seen_at),
+                last_seen_at = GREATEST(system_collisions.last_seen_at, EXCLUDED.last_seen_at),
+                count = system_collisions.count + 1
+            RETURNING existing_address
+            "#,
+            address as i64,
+            name,
+            wkb::Encode(position) as _,
+            seen_at.naive_utc())
+            .fetch_optional(&db.pool)
+            .await?;
+
+        Ok(row.is_some())
+    }
+
+    /// Keeps a faction reported in the colliding system `address`, unless a newer report of it
+    /// is kept already.
+    pub async fn add_faction(db: &Database,
+        address: u64,
+        faction_id: u32,
+        faction: &FactionInfo,
+        updated_at: DateTime<Utc>)
+        -> Result<(), Error>
+    {
+        sqlx::query!(
+            r#"
+            INSERT INTO system_collision_factions
+                (address,
+                 existing_address,
+                 faction_id,
+                 state,
+                 influence,
+                 happiness,
+                 government,
+                 allegiance,
+                 updated_at)
+            SELECT $1, existing_address, $2, $3, $4, $5, $6, $7, $8
+            FROM system_collisions
+            WHERE address = $1
+            ON CONFLICT (address, existing_address, faction_id)
+            DO UPDATE SET
+                state = $3,
+                influence = $4,
+                happiness = $5,
+                government = $6,
+                allegiance = $7,
+                updated_at = $8
+            WHERE system_collision_factions.updated_at < $8
+            "#,
+            address as i64,
+            faction_id as i32,
+            faction.state as _,
+            faction.influence,
+            faction.happiness as _,
+            faction.government as _,
+            faction.allegiance as _,
+            updated_at.naive_utc())
+            .execute(&db.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// The factions reported in this colliding system, by influence.
+    pub async fn factions(&self, db: &Database) -> Result<Vec<CollisionFaction>, Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                address,
+                existing_address,
+                name,
+                state AS "state: JournalState",
+                influence,
+                happiness AS "happiness: Happiness",
+                government AS "government: Government",
+                allegiance AS "allegiance: Allegiance",
+                updated_at
+            FROM system_collision_factions
+            JOIN factions ON faction_id = id
+            WHERE address = $1 AND existing_address = $2
+            ORDER BY influence DESC
+            "#, self.address, self.existing_address)
+            .fetch_all(&db.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| {
+            CollisionFaction {
+                address: row.address,
+                existing_address: row.existing_address,
+                faction: row.name,
+                state: row.state,
+                influence: row.influence,
+                happiness: row.happiness,
+                government: row.government,
+                allegiance: row.allegiance,
+                updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
+            }
+        }).collect())
+    }
+
+    /// Every logged collision, most recently seen first.
+    pub async fn fetch_all(db: &Database) -> Result<Vec<Self>, Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                c.address,
+                c.name,
+                c.position AS "position!: wkb::Decode<Coordinate>",
+                c.existing_address,
+                s.name AS existing_name,
+                c.first_seen_at,
+                c.last_seen_at,
+                c.count
+            FROM system_collisions c
+            JOIN systems s ON s.address = c.existing_address
+            ORDER BY c.last_seen_at DESC
+            "#)
+            .fetch_all(&db.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| {
+            Collision {
+                address: row.address,
+                name: row.name,
+                position: row.position.geometry.expect("not null or invalid"),
+                existing_address: row.existing_address,
+                existing_name: row.existing_name,
+                first_seen_at: DateTime::<Utc>::from_utc(row.first_seen_at, Utc),
+                last_seen_at: DateTime::<Utc>::from_utc(row.last_seen_at, Utc),
+                count: row.count,
+            }
+        }).collect())
+    }
+}
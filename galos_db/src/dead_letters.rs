@@ -0,0 +1,93 @@
+//! EDDN messages which couldn't be parsed or written, see `galos-sync eddn --reprocess`.
+
+use chrono::{DateTime, Utc};
+use crate::{Error, Database};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadLetter {
+    pub id: i32,
+    pub schema_ref: Option<String>,
+    /// The message's JSON, as received.
+    pub raw: String,
+    pub error: String,
+    pub received_at: DateTime<Utc>,
+    pub reprocessed_at: Option<DateTime<Utc>>,
+}
+
+impl DeadLetter {
+    pub async fn create(db: &Database, schema_ref: Option<&str>, raw: &str, error: &str)
+        -> Result<Self, Error>
+    {
+        let received_at = Utc::now();
+        let row = sqlx::query!(
+            r#"
+            INSERT INTO eddn_dead_letters (schema_ref, raw, error, received_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+            "#,
+            schema_ref,
+            raw,
+            error,
+            received_at.naive_utc())
+            .fetch_one(&db.pool)
+            .await?;
+
+        Ok(DeadLetter {
+            id: row.id,
+            schema_ref: schema_ref.map(|s| s.to_string()),
+            raw: raw.to_string(),
+            error: error.to_string(),
+            received_at,
+            reprocessed_at: None,
+        })
+    }
+
+    /// The oldest messages after `after_id` which haven't been reprocessed yet.
+    pub async fn fetch_pending(db: &Database, after_id: i32, limit: i64) -> Result<Vec<Self>, Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT *
+            FROM eddn_dead_letters
+            WHERE reprocessed_at IS NULL
+              AND id > $1
+            ORDER BY id
+            LIMIT $2
+            "#, after_id, limit)
+            .fetch_all(&db.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| {
+            DeadLetter {
+                id: row.id,
+                schema_ref: row.schema_ref,
+                raw: row.raw,
+                error: row.error,
+                received_at: DateTime::<Utc>::from_utc(row.received_at, Utc),
+                reprocessed_at: row.reprocessed_at.map(|t| DateTime::<Utc>::from_utc(t, Utc)),
+            }
+        }).collect())
+    }
+
+    /// Marks the message as reprocessed, or records why it failed again.
+    pub async fn reprocessed(&mut self, db: &Database, error: Option<&str>) -> Result<(), Error> {
+        match error {
+            None => self.reprocessed_at = Some(Utc::now()),
+            Some(error) => self.error = error.to_string(),
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE eddn_dead_letters
+            SET error = $2,
+                reprocessed_at = $3
+            WHERE id = $1
+            "#,
+            self.id,
+            self.error,
+            self.reprocessed_at.map(|t| t.naive_utc()))
+            .execute(&db.pool)
+            .await?;
+
+        Ok(())
+    }
+}
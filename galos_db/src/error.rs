@@ -1,4 +1,4 @@
-use std::{env, fmt, error};
+use std::{env, fmt, error, io};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -6,6 +6,14 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub enum Error {
     Env(dotenv::Error),
     Sqlx(sqlx::Error),
+    Io(io::Error),
+}
+
+impl Error {
+    /// True when a query expecting a row found none, e.g. fetching by an unknown name.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Error::Sqlx(sqlx::Error::RowNotFound))
+    }
 }
 
 impl fmt::Display for Error {
@@ -16,6 +24,8 @@ impl fmt::Display for Error {
                 write!(f, "{}", e),
             Error::Sqlx(e) =>
                 write!(f, "{}", e),
+            Error::Io(e) =>
+                write!(f, "{}", e),
         }
     }
 }
@@ -25,6 +35,7 @@ impl error::Error for Error {
         match *self {
             Error::Env(ref e) => Some(e),
             Error::Sqlx(ref e) => Some(e),
+            Error::Io(ref e) => Some(e),
         }
     }
 }
@@ -46,3 +57,9 @@ impl From<sqlx::Error> for Error {
         Error::Sqlx(err)
     }
 }
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
@@ -80,12 +80,19 @@ pub struct SystemFaction {
 }
 
 impl SystemFaction {
-    pub async fn from_journal(
+    /// Inserts or updates a faction's presence in a system, along with its pending, active and
+    /// recovering states, unless the stored presence is newer.
+    pub async fn create(
         db: &Database,
         system_address: u64,
         faction_id: u32,
-        faction_info: &FactionInfo,
-        timestamp: DateTime<Utc>)
+        state: Option<JournalState>,
+        influence: f32,
+        happiness: Option<Happiness>,
+        government: Government,
+        allegiance: Allegiance,
+        states: &[(JournalState, Status)],
+        updated_at: DateTime<Utc>)
         -> Result<Option<Self>, Error>
     {
         let row = sqlx::query!(
@@ -119,31 +126,20 @@ impl SystemFaction {
             "#,
                 system_address as i64,
                 faction_id as i32,
-                faction_info.state as _,
-                faction_info.influence,
-                faction_info.happiness as _,
-                faction_info.government as _,
-                faction_info.allegiance as _,
-                timestamp.naive_utc())
+                state as _,
+                influence,
+                happiness as _,
+                government as _,
+                allegiance as _,
+                updated_at.naive_utc())
             .fetch_optional(&db.pool)
             .await?;
 
         if let Some(r) = row {
             State::clear(db, system_address, faction_id).await?;
 
-            for state_trend in &faction_info.pending_states {
-                State::from_journal(db,
-                    system_address, faction_id, state_trend.state, Status::Pending).await?;
-            }
-
-            for state_trend in &faction_info.active_states {
-                State::from_journal(db,
-                    system_address, faction_id, state_trend.state, Status::Active).await?;
-            }
-
-            for state_trend in &faction_info.recovering_states {
-                State::from_journal(db,
-                    system_address, faction_id, state_trend.state, Status::Recovering).await?;
+            for &(state, status) in states {
+                State::from_journal(db, system_address, faction_id, state, status).await?;
             }
 
             Ok(Some(SystemFaction {
@@ -159,6 +155,32 @@ impl SystemFaction {
         }
     }
 
+    pub async fn from_journal(
+        db: &Database,
+        system_address: u64,
+        faction_id: u32,
+        faction_info: &FactionInfo,
+        timestamp: DateTime<Utc>)
+        -> Result<Option<Self>, Error>
+    {
+        let states: Vec<(JournalState, Status)> = faction_info.pending_states.iter()
+            .map(|s| (s.state, Status::Pending))
+            .chain(faction_info.active_states.iter().map(|s| (s.state, Status::Active)))
+            .chain(faction_info.recovering_states.iter().map(|s| (s.state, Status::Recovering)))
+            .collect();
+
+        SystemFaction::create(db,
+            system_address,
+            faction_id,
+            faction_info.state,
+            faction_info.influence,
+            faction_info.happiness,
+            faction_info.government,
+            faction_info.allegiance,
+            &states,
+            timestamp).await
+    }
+
     pub async fn fetch(db: &Database, address: u64, id: u32) -> Result<Self, Error> {
         let row = sqlx::query!(
             r#"
@@ -256,6 +278,71 @@ impl SystemFaction {
             }).collect())
         }
     }
+
+    /// The addresses of every system with any of the named factions present, matching names
+    /// case insensitively.
+    pub async fn fetch_addresses_by_faction(db: &Database, names: &[String]) -> Result<Vec<i64>, Error> {
+        let names: Vec<String> = names.iter().map(|n| n.to_lowercase()).collect();
+        let rows = sqlx::query!(
+            r#"
+            SELECT DISTINCT system_address
+            FROM system_factions
+            JOIN factions ON faction_id = id
+            WHERE lower(name) = ANY($1)
+            "#, &names[..])
+            .fetch_all(&db.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| row.system_address).collect())
+    }
+
+    /// The faction's pending, active and recovering states over time, in one system or every
+    /// system it's been in, ordered by system then start.
+    pub async fn state_timeline(db: &Database, system_address: Option<u64>, faction_id: u32)
+        -> Result<Vec<StateSpan>, Error>
+    {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                system_address,
+                faction_id,
+                state AS "state: JournalState",
+                status AS "status: Status",
+                started_at,
+                ended_at
+            FROM system_faction_state_history
+            WHERE faction_id = $2
+              AND ($1::bigint IS NULL OR system_address = $1)
+            ORDER BY system_address, started_at
+            "#,
+            system_address.map(|a| a as i64),
+            faction_id as i32)
+            .fetch_all(&db.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| {
+            StateSpan {
+                system_address: row.system_address as u64,
+                faction_id: row.faction_id as u32,
+                state: row.state,
+                status: row.status,
+                started_at: DateTime::<Utc>::from_utc(row.started_at, Utc),
+                ended_at: row.ended_at.map(|t| DateTime::<Utc>::from_utc(t, Utc)),
+            }
+        }).collect())
+    }
+}
+
+/// A period a faction spent in a state, see `SystemFaction::state_timeline`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateSpan {
+    pub system_address: u64,
+    pub faction_id: u32,
+    pub state: JournalState,
+    pub status: Status,
+    pub started_at: DateTime<Utc>,
+    /// `None` while the state is current.
+    pub ended_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -304,6 +391,7 @@ impl State {
         })
     }
 
+    /// Removes the faction's current states. Their history is kept, see `StateSpan`.
     pub async fn clear(db: &Database, system_address: u64, faction_id: u32) -> Result<(), Error> {
         sqlx::query!(
             r#"
@@ -0,0 +1,139 @@
+//! Progress of file imports, so an interrupted import can be resumed.
+
+use chrono::{DateTime, Utc};
+use crate::{Error, Database};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportRun {
+    pub id: i32,
+    pub source: String,
+    pub path: String,
+    /// Identifies the file along with its path, from a hash of its start.
+    pub file_hash: String,
+    /// The file's size in bytes, when the run was started or last resumed.
+    pub file_size: i64,
+    /// Rows, or journal entries, read so far.
+    pub position: i64,
+    pub processed: i64,
+    pub failed: i64,
+    pub finished: bool,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl ImportRun {
+    pub async fn create(db: &Database, source: &str, path: &str, file_hash: &str, file_size: i64)
+        -> Result<Self, Error>
+    {
+        let now = Utc::now();
+        let row = sqlx::query!(
+            r#"
+            INSERT INTO import_runs (source, path, file_hash, file_size, started_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $5)
+            RETURNING id
+            "#,
+            source,
+            path,
+            file_hash,
+            file_size,
+            now.naive_utc())
+            .fetch_one(&db.pool)
+            .await?;
+
+        Ok(ImportRun {
+            id: row.id,
+            source: source.to_string(),
+            path: path.to_string(),
+            file_hash: file_hash.to_string(),
+            file_size,
+            position: 0,
+            processed: 0,
+            failed: 0,
+            finished: false,
+            started_at: now,
+            updated_at: now,
+        })
+    }
+
+    /// The most recent run importing the file at `path` with the given hash.
+    pub async fn fetch_latest(db: &Database, source: &str, path: &str, file_hash: &str)
+        -> Result<Option<Self>, Error>
+    {
+        let row = sqlx::query!(
+            r#"
+            SELECT *
+            FROM import_runs
+            WHERE source = $1 AND path = $2 AND file_hash = $3
+            ORDER BY finished DESC, updated_at DESC
+            LIMIT 1
+            "#, source, path, file_hash)
+            .fetch_optional(&db.pool)
+            .await?;
+
+        Ok(row.map(|row| {
+            ImportRun {
+                id: row.id,
+                source: row.source,
+                path: row.path,
+                file_hash: row.file_hash,
+                file_size: row.file_size,
+                position: row.position,
+                processed: row.processed,
+                failed: row.failed,
+                finished: row.finished,
+                started_at: DateTime::<Utc>::from_utc(row.started_at, Utc),
+                updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
+            }
+        }))
+    }
+
+    /// Records that everything before `position` has been imported.
+    pub async fn checkpoint(&mut self, db: &Database, position: i64, processed: i64, failed: i64)
+        -> Result<(), Error>
+    {
+        self.position = position;
+        self.processed = processed;
+        self.failed = failed;
+        self.updated_at = Utc::now();
+        self.save(db).await
+    }
+
+    /// Continues the run, now that the file is `file_size` bytes.
+    pub async fn resume(&mut self, db: &Database, file_size: i64) -> Result<(), Error> {
+        self.file_size = file_size;
+        self.finished = false;
+        self.updated_at = Utc::now();
+        self.save(db).await
+    }
+
+    pub async fn finish(&mut self, db: &Database) -> Result<(), Error> {
+        self.finished = true;
+        self.updated_at = Utc::now();
+        self.save(db).await
+    }
+
+    async fn save(&self, db: &Database) -> Result<(), Error> {
+        sqlx::query!(
+            r#"
+            UPDATE import_runs
+            SET position = $2,
+                processed = $3,
+                failed = $4,
+                finished = $5,
+                updated_at = $6,
+                file_size = $7
+            WHERE id = $1
+            "#,
+            self.id,
+            self.position,
+            self.processed,
+            self.failed,
+            self.finished,
+            self.updated_at.naive_utc(),
+            self.file_size)
+            .execute(&db.pool)
+            .await?;
+
+        Ok(())
+    }
+}
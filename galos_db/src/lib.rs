@@ -53,4 +53,16 @@ impl Page {
 
 pub mod articles;
 pub mod systems;
+pub mod system_names;
+pub mod collisions;
 pub mod factions;
+pub mod bodies;
+pub mod stations;
+pub mod markets;
+pub mod outfitting;
+pub mod signals;
+pub mod settlements;
+pub mod spatial;
+pub mod bulk;
+pub mod imports;
+pub mod dead_letters;
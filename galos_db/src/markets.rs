@@ -0,0 +1,251 @@
+use chrono::{DateTime, Utc};
+use crate::{Error, Database};
+use crate::stations::LandingPad;
+
+/// A single commodity's price at a market. Commodity names are the lowercase symbols used by
+/// the journal and EDDN, e.g. `gold` or `lowtemperaturediamond`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketPrice {
+    pub market_id: u64,
+    pub commodity: String,
+    /// What the market charges, 0 when it doesn't sell the commodity.
+    pub buy_price: u32,
+    /// What the market pays.
+    pub sell_price: u32,
+    pub stock: u32,
+    pub demand: u32,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A price at a station near some system, see `MarketPrice::fetch_best_buys`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Listing {
+    pub system_name: String,
+    pub station_name: String,
+    /// Light years from the system searched around.
+    pub distance: f64,
+    pub price: MarketPrice,
+}
+
+/// A price at a station, see `MarketPrice::fetch_in_systems`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StationPrice {
+    pub system_address: u64,
+    pub station_name: String,
+    pub landing_pad: Option<LandingPad>,
+    pub price: MarketPrice,
+}
+
+impl MarketPrice {
+    /// Replaces every price at a market with a newer snapshot of the whole market. Older
+    /// snapshots are ignored.
+    pub async fn snapshot(db: &Database, market_id: u64, prices: &[MarketPrice], timestamp: DateTime<Utc>)
+        -> Result<bool, Error>
+    {
+        let mut tx = db.pool.begin().await?;
+
+        let newer = sqlx::query!(
+            r#"
+            SELECT max(updated_at) >= $2 AS "newer!"
+            FROM market_prices
+            WHERE market_id = $1
+            HAVING count(*) > 0
+            "#,
+            market_id as i64,
+            timestamp.naive_utc())
+            .fetch_optional(&mut tx)
+            .await?;
+
+        if newer.map_or(false, |row| row.newer) {
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            r#"
+            DELETE FROM market_prices
+            WHERE market_id = $1
+            "#,
+            market_id as i64)
+            .execute(&mut tx)
+            .await?;
+
+        for price in prices {
+            sqlx::query!(
+                r#"
+                INSERT INTO market_prices
+                    (market_id,
+                     commodity,
+                     buy_price,
+                     sell_price,
+                     stock,
+                     demand,
+                     updated_at)
+                VALUES ($1, LOWER($2), $3, $4, $5, $6, $7)
+                "#,
+                market_id as i64,
+                price.commodity,
+                price.buy_price as i32,
+                price.sell_price as i32,
+                price.stock as i32,
+                price.demand as i32,
+                timestamp.naive_utc())
+                .execute(&mut tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    pub async fn fetch_all(db: &Database, market_id: u64) -> Result<Vec<Self>, Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT *
+            FROM market_prices
+            WHERE market_id = $1
+            ORDER BY commodity
+            "#, market_id as i64)
+            .fetch_all(&db.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| {
+            MarketPrice {
+                market_id: row.market_id as u64,
+                commodity: row.commodity,
+                buy_price: row.buy_price as u32,
+                sell_price: row.sell_price as u32,
+                stock: row.stock as u32,
+                demand: row.demand as u32,
+                updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
+            }
+        }).collect())
+    }
+
+    /// The cheapest places to buy `commodity` within `radius` Ly of a system.
+    pub async fn fetch_best_buys(db: &Database, system_address: u64, radius: f64, commodity: &str, limit: i64)
+        -> Result<Vec<Listing>, Error>
+    {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                s1.name AS system_name,
+                stations.name AS station_name,
+                ST_3DDistance(s1.position, s2.position) AS "distance!",
+                market_prices.*
+            FROM market_prices
+            JOIN stations USING (market_id)
+            JOIN systems s1 ON stations.system_address = s1.address
+            JOIN systems s2 ON ST_3DDWithin(s1.position, s2.position, $2)
+            WHERE s2.address = $1
+              AND commodity = LOWER($3)
+              AND buy_price > 0
+              AND stock > 0
+            ORDER BY buy_price, ST_3DDistance(s1.position, s2.position)
+            LIMIT $4
+            "#, system_address as i64, radius, commodity, limit)
+            .fetch_all(&db.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| {
+            Listing {
+                system_name: row.system_name,
+                station_name: row.station_name,
+                distance: row.distance,
+                price: MarketPrice {
+                    market_id: row.market_id as u64,
+                    commodity: row.commodity,
+                    buy_price: row.buy_price as u32,
+                    sell_price: row.sell_price as u32,
+                    stock: row.stock as u32,
+                    demand: row.demand as u32,
+                    updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
+                },
+            }
+        }).collect())
+    }
+
+    /// The best paying places to sell `commodity` within `radius` Ly of a system.
+    pub async fn fetch_best_sells(db: &Database, system_address: u64, radius: f64, commodity: &str, limit: i64)
+        -> Result<Vec<Listing>, Error>
+    {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                s1.name AS system_name,
+                stations.name AS station_name,
+                ST_3DDistance(s1.position, s2.position) AS "distance!",
+                market_prices.*
+            FROM market_prices
+            JOIN stations USING (market_id)
+            JOIN systems s1 ON stations.system_address = s1.address
+            JOIN systems s2 ON ST_3DDWithin(s1.position, s2.position, $2)
+            WHERE s2.address = $1
+              AND commodity = LOWER($3)
+              AND sell_price > 0
+            ORDER BY sell_price DESC, ST_3DDistance(s1.position, s2.position)
+            LIMIT $4
+            "#, system_address as i64, radius, commodity, limit)
+            .fetch_all(&db.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| {
+            Listing {
+                system_name: row.system_name,
+                station_name: row.station_name,
+                distance: row.distance,
+                price: MarketPrice {
+                    market_id: row.market_id as u64,
+                    commodity: row.commodity,
+                    buy_price: row.buy_price as u32,
+                    sell_price: row.sell_price as u32,
+                    stock: row.stock as u32,
+                    demand: row.demand as u32,
+                    updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
+                },
+            }
+        }).collect())
+    }
+
+    /// Every price at stations in the given systems, which have a landing pad at least as
+    /// large as `pad` and were updated since `since`.
+    pub async fn fetch_in_systems(
+        db: &Database,
+        addresses: &[i64],
+        pad: Option<LandingPad>,
+        since: DateTime<Utc>)
+        -> Result<Vec<StationPrice>, Error>
+    {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                stations.system_address,
+                stations.name AS station_name,
+                stations.landing_pad AS "landing_pad: LandingPad",
+                market_prices.*
+            FROM market_prices
+            JOIN stations USING (market_id)
+            WHERE stations.system_address = ANY($1)
+              AND ($2::landingpad IS NULL OR stations.landing_pad >= $2)
+              AND market_prices.updated_at >= $3
+            "#, addresses, pad as _, since.naive_utc())
+            .fetch_all(&db.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| {
+            StationPrice {
+                system_address: row.system_address as u64,
+                station_name: row.station_name,
+                landing_pad: row.landing_pad,
+                price: MarketPrice {
+                    market_id: row.market_id as u64,
+                    commodity: row.commodity,
+                    buy_price: row.buy_price as u32,
+                    sell_price: row.sell_price as u32,
+                    stock: row.stock as u32,
+                    demand: row.demand as u32,
+                    updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
+                },
+            }
+        }).collect())
+    }
+}
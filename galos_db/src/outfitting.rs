@@ -0,0 +1,294 @@
+//! Module (outfitting) and ship (shipyard) availability at stations.
+//!
+//! Modules and ships are stored by their lowercase journal symbols, like
+//! `int_hyperdrive_size5_class5` and `krait_mkii`. The `module_symbol` and `ship_symbol`
+//! functions translate the in-game names players know into these.
+
+use chrono::{DateTime, Utc};
+use crate::{Error, Database};
+
+/// A station selling some module or ship, see `Outfitting::fetch_modules_near`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Availability {
+    pub market_id: u64,
+    pub system_name: String,
+    pub station_name: String,
+    /// The module or ship's symbol.
+    pub item: String,
+    /// Light years from the system searched around.
+    pub distance: f64,
+    pub updated_at: DateTime<Utc>,
+}
+
+pub struct Outfitting;
+
+impl Outfitting {
+    /// Replaces the modules sold at a market with a newer snapshot. Older snapshots are
+    /// ignored.
+    pub async fn snapshot_modules(db: &Database, market_id: u64, modules: &[String], timestamp: DateTime<Utc>)
+        -> Result<bool, Error>
+    {
+        let mut tx = db.pool.begin().await?;
+
+        let newer = sqlx::query!(
+            r#"
+            SELECT max(updated_at) >= $2 AS "newer!"
+            FROM station_modules
+            WHERE market_id = $1
+            HAVING count(*) > 0
+            "#,
+            market_id as i64,
+            timestamp.naive_utc())
+            .fetch_optional(&mut tx)
+            .await?;
+
+        if newer.map_or(false, |row| row.newer) {
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            r#"
+            DELETE FROM station_modules
+            WHERE market_id = $1
+            "#,
+            market_id as i64)
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO station_modules (market_id, module, updated_at)
+            SELECT $1, LOWER(module), $3
+            FROM UNNEST($2::varchar[]) AS module
+            ON CONFLICT DO NOTHING
+            "#,
+            market_id as i64,
+            modules,
+            timestamp.naive_utc())
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Replaces the ships sold at a market with a newer snapshot. Older snapshots are ignored.
+    pub async fn snapshot_ships(db: &Database, market_id: u64, ships: &[String], timestamp: DateTime<Utc>)
+        -> Result<bool, Error>
+    {
+        let mut tx = db.pool.begin().await?;
+
+        let newer = sqlx::query!(
+            r#"
+            SELECT max(updated_at) >= $2 AS "newer!"
+            FROM station_ships
+            WHERE market_id = $1
+            HAVING count(*) > 0
+            "#,
+            market_id as i64,
+            timestamp.naive_utc())
+            .fetch_optional(&mut tx)
+            .await?;
+
+        if newer.map_or(false, |row| row.newer) {
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            r#"
+            DELETE FROM station_ships
+            WHERE market_id = $1
+            "#,
+            market_id as i64)
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO station_ships (market_id, ship, updated_at)
+            SELECT $1, LOWER(ship), $3
+            FROM UNNEST($2::varchar[]) AS ship
+            ON CONFLICT DO NOTHING
+            "#,
+            market_id as i64,
+            ships,
+            timestamp.naive_utc())
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// The nearest stations to a system selling modules matching the `LIKE` pattern `symbol`,
+    /// optionally within `radius` Ly.
+    pub async fn fetch_modules_near(
+        db: &Database,
+        system_address: u64,
+        radius: Option<f64>,
+        symbol: &str,
+        limit: i64)
+        -> Result<Vec<Availability>, Error>
+    {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                m.market_id,
+                s1.name AS system_name,
+                stations.name AS station_name,
+                m.module AS item,
+                ST_3DDistance(s1.position, s2.position) AS "distance!",
+                m.updated_at
+            FROM station_modules m
+            JOIN stations USING (market_id)
+            JOIN systems s1 ON stations.system_address = s1.address
+            JOIN systems s2 ON s2.address = $1
+            WHERE m.module LIKE LOWER($3)
+              AND ($2::float8 IS NULL OR ST_3DDWithin(s1.position, s2.position, $2))
+            ORDER BY ST_3DDistance(s1.position, s2.position)
+            LIMIT $4
+            "#, system_address as i64, radius, symbol, limit)
+            .fetch_all(&db.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| {
+            Availability {
+                market_id: row.market_id as u64,
+                system_name: row.system_name,
+                station_name: row.station_name,
+                item: row.item,
+                distance: row.distance,
+                updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
+            }
+        }).collect())
+    }
+
+    /// The nearest stations to a system selling ships matching the `LIKE` pattern `symbol`,
+    /// optionally within `radius` Ly.
+    pub async fn fetch_ships_near(
+        db: &Database,
+        system_address: u64,
+        radius: Option<f64>,
+        symbol: &str,
+        limit: i64)
+        -> Result<Vec<Availability>, Error>
+    {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                sh.market_id,
+                s1.name AS system_name,
+                stations.name AS station_name,
+                sh.ship AS item,
+                ST_3DDistance(s1.position, s2.position) AS "distance!",
+                sh.updated_at
+            FROM station_ships sh
+            JOIN stations USING (market_id)
+            JOIN systems s1 ON stations.system_address = s1.address
+            JOIN systems s2 ON s2.address = $1
+            WHERE sh.ship LIKE LOWER($3)
+              AND ($2::float8 IS NULL OR ST_3DDWithin(s1.position, s2.position, $2))
+            ORDER BY ST_3DDistance(s1.position, s2.position)
+            LIMIT $4
+            "#, system_address as i64, radius, symbol, limit)
+            .fetch_all(&db.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| {
+            Availability {
+                market_id: row.market_id as u64,
+                system_name: row.system_name,
+                station_name: row.station_name,
+                item: row.item,
+                distance: row.distance,
+                updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
+            }
+        }).collect())
+    }
+}
+
+/// Translates a module name like "5A Frame Shift Drive" into a `LIKE` pattern for its symbol,
+/// `int_hyperdrive_size5_class5`. Names without a known symbol match any symbol containing
+/// their words, in order.
+pub fn module_symbol(name: &str) -> String {
+    let mut words = name.split_whitespace();
+    let rating = words.clone().next().and_then(|w| {
+        let mut chars = w.chars();
+        let size = chars.next()?.to_digit(10)?;
+        let class = match chars.next()?.to_ascii_uppercase() {
+            'A' => 5,
+            'B' => 4,
+            'C' => 3,
+            'D' => 2,
+            'E' => 1,
+            _ => return None,
+        };
+        if chars.next().is_some() { None } else { Some((size, class)) }
+    });
+    if rating.is_some() {
+        words.next();
+    }
+    let rest = words.collect::<Vec<_>>().join(" ").to_lowercase();
+
+    let group = match rest.as_str() {
+        "frame shift drive" => Some("hyperdrive"),
+        "power plant" => Some("powerplant"),
+        "thrusters" => Some("engine"),
+        "life support" => Some("lifesupport"),
+        "power distributor" => Some("powerdistributor"),
+        "sensors" => Some("sensors"),
+        "fuel tank" => Some("fueltank"),
+        "fuel scoop" => Some("fuelscoop"),
+        "shield generator" => Some("shieldgenerator"),
+        "shield cell bank" => Some("shieldcellbank"),
+        "cargo rack" => Some("cargorack"),
+        "auto field-maintenance unit" => Some("repairer"),
+        "hull reinforcement package" => Some("hullreinforcement"),
+        "module reinforcement package" => Some("modulereinforcement"),
+        "frame shift drive interdictor" => Some("fsdinterdictor"),
+        "refinery" => Some("refinery"),
+        "detailed surface scanner" => Some("detailedsurfacescanner"),
+        _ => None,
+    };
+
+    match (group, rating) {
+        (Some(group), Some((size, class))) => format!("int_{}_size{}_class{}", group, size, class),
+        (Some(group), None) => format!("int_{}%", group),
+        (None, _) => format!("%{}%", rest.split_whitespace().collect::<Vec<_>>().join("%")),
+    }
+}
+
+/// Translates a ship name like "Krait MkII" into a `LIKE` pattern for its symbol, `krait_mkii`.
+pub fn ship_symbol(name: &str) -> String {
+    let symbol = match name.to_lowercase().as_str() {
+        "alliance challenger" => "typex_3",
+        "alliance chieftain" => "typex",
+        "alliance crusader" => "typex_2",
+        "asp explorer" => "asp",
+        "asp scout" => "asp_scout",
+        "beluga liner" => "belugaliner",
+        "diamondback explorer" => "diamondbackxl",
+        "diamondback scout" => "diamondback",
+        "federal assault ship" => "federation_dropship_mkii",
+        "federal corvette" => "federation_corvette",
+        "federal dropship" => "federation_dropship",
+        "federal gunship" => "federation_gunship",
+        "fer-de-lance" => "ferdelance",
+        "imperial clipper" => "empire_trader",
+        "imperial courier" => "empire_courier",
+        "imperial cutter" => "cutter",
+        "imperial eagle" => "empire_eagle",
+        "keelback" => "independant_trader",
+        "krait mkii" => "krait_mkii",
+        "krait phantom" => "krait_light",
+        "type-6 transporter" => "type6",
+        "type-7 transporter" => "type7",
+        "type-9 heavy" => "type9",
+        "type-10 defender" => "type9_military",
+        "viper mkiii" => "viper",
+        "viper mkiv" => "viper_mkiv",
+        other => return other.split_whitespace().collect::<Vec<_>>().join("%"),
+    };
+
+    symbol.to_string()
+}
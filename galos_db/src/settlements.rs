@@ -0,0 +1,86 @@
+use chrono::{DateTime, Utc};
+use elite_journal::entry::settlement::ApproachSettlement;
+use crate::{Error, Database};
+
+/// A surface settlement, which may have a market.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settlement {
+    pub system_address: u64,
+    pub body_id: u16,
+    pub name: String,
+    pub market_id: Option<u64>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Settlement {
+    pub async fn from_journal(db: &Database, approach: &ApproachSettlement, timestamp: DateTime<Utc>)
+        -> Result<Self, Error>
+    {
+        let settlement = Settlement {
+            system_address: approach.system_address,
+            body_id: approach.body_id,
+            name: approach.name.clone(),
+            market_id: approach.market_id,
+            latitude: approach.latitude,
+            longitude: approach.longitude,
+            updated_at: timestamp,
+        };
+
+        sqlx::query!(
+            r#"
+            INSERT INTO settlements
+                (system_address,
+                 body_id,
+                 name,
+                 market_id,
+                 latitude,
+                 longitude,
+                 updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (system_address, body_id, name)
+            DO UPDATE SET
+                market_id = COALESCE($4, settlements.market_id),
+                latitude = COALESCE($5, settlements.latitude),
+                longitude = COALESCE($6, settlements.longitude),
+                updated_at = $7
+            WHERE settlements.updated_at < $7
+            "#,
+            settlement.system_address as i64,
+            settlement.body_id as i16,
+            settlement.name,
+            settlement.market_id.map(|id| id as i64),
+            settlement.latitude,
+            settlement.longitude,
+            settlement.updated_at.naive_utc())
+            .execute(&db.pool)
+            .await?;
+
+        Ok(settlement)
+    }
+
+    pub async fn fetch_all(db: &Database, system_address: u64) -> Result<Vec<Self>, Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT *
+            FROM settlements
+            WHERE system_address = $1
+            ORDER BY body_id, name
+            "#, system_address as i64)
+            .fetch_all(&db.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| {
+            Settlement {
+                system_address: row.system_address as u64,
+                body_id: row.body_id as u16,
+                name: row.name,
+                market_id: row.market_id.map(|id| id as u64),
+                latitude: row.latitude,
+                longitude: row.longitude,
+                updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
+            }
+        }).collect())
+    }
+}
@@ -0,0 +1,188 @@
+use chrono::{DateTime, Duration, Utc};
+use elite_journal::entry::{fss::FssSignalDiscovered, saa::SaaSignalsFound};
+use crate::{Error, Database};
+
+/// A signal source in a system, found with the FSS.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemSignal {
+    pub system_address: u64,
+    pub name: String,
+    pub is_station: bool,
+    pub uss_type: Option<String>,
+    pub threat_level: Option<u8>,
+    pub updated_at: DateTime<Utc>,
+    /// When a temporary signal source, like a USS, disappears.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Signals on a body's surface, found by mapping it, e.g. 3 `Biological` signals.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BodySignal {
+    pub system_address: u64,
+    pub body_id: u16,
+    pub ty: String,
+    pub count: u32,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl SystemSignal {
+    pub async fn from_journal(db: &Database, signal: &FssSignalDiscovered, timestamp: DateTime<Utc>)
+        -> Result<Self, Error>
+    {
+        let signal = SystemSignal {
+            system_address: signal.system_address,
+            name: signal.signal_name.clone(),
+            is_station: signal.is_station.unwrap_or(false),
+            uss_type: signal.uss_type.clone(),
+            threat_level: signal.threat_level,
+            updated_at: timestamp,
+            expires_at: signal.time_remaining
+                .map(|seconds| timestamp + Duration::milliseconds((seconds * 1000.) as i64)),
+        };
+
+        sqlx::query!(
+            r#"
+            INSERT INTO system_signals
+                (system_address,
+                 name,
+                 is_station,
+                 uss_type,
+                 threat_level,
+                 updated_at,
+                 expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (system_address, name)
+            DO UPDATE SET
+                is_station = $3,
+                uss_type = $4,
+                threat_level = $5,
+                updated_at = $6,
+                expires_at = $7
+            WHERE system_signals.updated_at < $6
+            "#,
+            signal.system_address as i64,
+            signal.name,
+            signal.is_station,
+            signal.uss_type,
+            signal.threat_level.map(|n| n as i16),
+            signal.updated_at.naive_utc(),
+            signal.expires_at.map(|t| t.naive_utc()))
+            .execute(&db.pool)
+            .await?;
+
+        Ok(signal)
+    }
+
+    /// Signals in a system which haven't expired.
+    pub async fn fetch_all(db: &Database, system_address: u64) -> Result<Vec<Self>, Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT *
+            FROM system_signals
+            WHERE system_address = $1
+              AND (expires_at IS NULL OR expires_at > now() AT TIME ZONE 'utc')
+            ORDER BY name
+            "#, system_address as i64)
+            .fetch_all(&db.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| {
+            SystemSignal {
+                system_address: row.system_address as u64,
+                name: row.name,
+                is_station: row.is_station,
+                uss_type: row.uss_type,
+                threat_level: row.threat_level.map(|n| n as u8),
+                updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
+                expires_at: row.expires_at.map(|t| DateTime::<Utc>::from_utc(t, Utc)),
+            }
+        }).collect())
+    }
+}
+
+impl BodySignal {
+    /// Replaces a body's signals, unless they were found more recently.
+    pub async fn from_journal(db: &Database, found: &SaaSignalsFound, timestamp: DateTime<Utc>)
+        -> Result<Vec<Self>, Error>
+    {
+        let mut tx = db.pool.begin().await?;
+
+        let newer = sqlx::query!(
+            r#"
+            SELECT max(updated_at) >= $3 AS "newer!"
+            FROM body_signals
+            WHERE system_address = $1 AND body_id = $2
+            HAVING count(*) > 0
+            "#,
+            found.system_address as i64,
+            found.body_id as i16,
+            timestamp.naive_utc())
+            .fetch_optional(&mut tx)
+            .await?;
+
+        if newer.map_or(false, |row| row.newer) {
+            return Ok(vec![]);
+        }
+
+        sqlx::query!(
+            r#"
+            DELETE FROM body_signals
+            WHERE system_address = $1 AND body_id = $2
+            "#,
+            found.system_address as i64,
+            found.body_id as i16)
+            .execute(&mut tx)
+            .await?;
+
+        let mut signals = Vec::new();
+        for signal in &found.signals {
+            let signal = BodySignal {
+                system_address: found.system_address,
+                body_id: found.body_id,
+                ty: signal.ty.clone(),
+                count: signal.count,
+                updated_at: timestamp,
+            };
+
+            sqlx::query!(
+                r#"
+                INSERT INTO body_signals (system_address, body_id, type, count, updated_at)
+                VALUES ($1, $2, $3, $4, $5)
+                "#,
+                signal.system_address as i64,
+                signal.body_id as i16,
+                signal.ty,
+                signal.count as i32,
+                signal.updated_at.naive_utc())
+                .execute(&mut tx)
+                .await?;
+
+            signals.push(signal);
+        }
+
+        tx.commit().await?;
+        Ok(signals)
+    }
+
+    pub async fn fetch_all(db: &Database, system_address: u64) -> Result<Vec<Self>, Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT system_address, body_id, type AS ty, count, updated_at
+            FROM body_signals
+            WHERE system_address = $1
+            ORDER BY body_id, type
+            "#, system_address as i64)
+            .fetch_all(&db.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| {
+            BodySignal {
+                system_address: row.system_address as u64,
+                body_id: row.body_id as u16,
+                ty: row.ty,
+                count: row.count as u32,
+                updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
+            }
+        }).collect())
+    }
+}
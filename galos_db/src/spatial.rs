@@ -0,0 +1,413 @@
+//! An in-memory spatial index over system positions, for route planning without a SQL query
+//! per A* expansion.
+//!
+//! Systems are bucketed into a uniform grid of cubic cells, so a range query only needs to look
+//! at the cells overlapping the query's bounding cube. The index can be saved to disk and
+//! reopened, which is much faster than loading it from the `systems` table each time. A saved
+//! index records how many systems there were and when the latest was updated, so it's rebuilt
+//! once the table changes.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use futures::TryStreamExt;
+use ordered_float::OrderedFloat;
+use pathfinding::prelude::*;
+use crate::{Error, Database};
+use crate::systems::{jump_boost, NEUTRON_BOOST};
+
+const MAGIC: &[u8; 8] = b"GALOSIDX";
+const VERSION: u32 = 3;
+
+/// A system's address and position, as stored in the index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub address: i64,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    /// The jump range multiplier when leaving this system, see `systems::jump_boost`.
+    pub boost: f32,
+}
+
+impl Point {
+    pub fn distance(&self, other: &Point) -> f64 {
+        ((other.x as f64 - self.x as f64).powi(2) +
+         (other.y as f64 - self.y as f64).powi(2) +
+         (other.z as f64 - self.z as f64).powi(2)).sqrt()
+    }
+}
+
+type Cell = (i32, i32, i32);
+
+pub struct SpatialIndex {
+    cell_size: f64,
+    /// The latest `updated_at` of the systems loaded.
+    updated_at: Option<DateTime<Utc>>,
+    points: Vec<Point>,
+    cells: HashMap<Cell, Vec<u32>>,
+    addresses: HashMap<i64, u32>,
+}
+
+impl SpatialIndex {
+    pub fn new(cell_size: f64, points: Vec<Point>) -> Self {
+        let mut index = SpatialIndex {
+            cell_size,
+            updated_at: None,
+            points: Vec::with_capacity(points.len()),
+            cells: HashMap::new(),
+            addresses: HashMap::with_capacity(points.len()),
+        };
+        for point in points {
+            index.insert(point);
+        }
+        index
+    }
+
+    fn insert(&mut self, point: Point) {
+        let i = self.points.len() as u32;
+        let cell = self.cell(point.x as f64, point.y as f64, point.z as f64);
+        self.cells.entry(cell).or_insert_with(Vec::new).push(i);
+        self.addresses.insert(point.address, i);
+        self.points.push(point);
+    }
+
+    fn cell(&self, x: f64, y: f64, z: f64) -> Cell {
+        ((x / self.cell_size).floor() as i32,
+         (y / self.cell_size).floor() as i32,
+         (z / self.cell_size).floor() as i32)
+    }
+
+    /// Loads every system's position from the database.
+    pub async fn load(db: &Database, cell_size: f64) -> Result<Self, Error> {
+        let mut index = SpatialIndex::new(cell_size, Vec::new());
+        let mut rows = sqlx::query!(
+            r#"
+            SELECT
+                address,
+                ST_X(position) AS "x!",
+                ST_Y(position) AS "y!",
+                ST_Z(position) AS "z!",
+                primary_star_class,
+                updated_at
+            FROM systems
+            "#)
+            .fetch(&db.pool);
+
+        while let Some(row) = rows.try_next().await? {
+            let updated_at = DateTime::<Utc>::from_utc(row.updated_at, Utc);
+            index.updated_at = index.updated_at.max(Some(updated_at));
+            index.insert(Point {
+                address: row.address,
+                x: row.x as f32,
+                y: row.y as f32,
+                z: row.z as f32,
+                boost: row.primary_star_class.as_deref().map_or(1., jump_boost) as f32,
+            });
+        }
+
+        Ok(index)
+    }
+
+    /// Opens the index cached at `path`, or loads it from the database and caches it there
+    /// when there's no cache, or it's stale.
+    pub async fn load_cached<P: AsRef<Path>>(db: &Database, path: P, cell_size: f64)
+        -> Result<Self, Error>
+    {
+        let cached = File::open(&path).and_then(|file| Header::read(&mut BufReader::new(file)));
+        if let Ok(header) = cached {
+            let row = sqlx::query!(
+                r#"
+                SELECT COUNT(*) AS "count!", MAX(updated_at) AS updated_at
+                FROM systems
+                "#)
+                .fetch_one(&db.pool)
+                .await?;
+
+            let current = Header {
+                cell_size,
+                updated_at: row.updated_at.map(|t| DateTime::<Utc>::from_utc(t, Utc)),
+                len: row.count as u64,
+            };
+            if header == current {
+                return Ok(SpatialIndex::open(path)?);
+            }
+        }
+
+        let index = SpatialIndex::load(db, cell_size).await?;
+        index.save(path)?;
+        Ok(index)
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+
+        let Header { cell_size, updated_at, len } = Header::read(&mut file)?;
+        let mut points = Vec::with_capacity(len as usize);
+        for _ in 0..len {
+            points.push(Point {
+                address: read_u64(&mut file)? as i64,
+                x: read_f32(&mut file)?,
+                y: read_f32(&mut file)?,
+                z: read_f32(&mut file)?,
+                boost: read_f32(&mut file)?,
+            });
+        }
+
+        let mut index = SpatialIndex::new(cell_size, points);
+        index.updated_at = updated_at;
+        Ok(index)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        Header {
+            cell_size: self.cell_size,
+            updated_at: self.updated_at,
+            len: self.points.len() as u64,
+        }.write(&mut file)?;
+        for point in &self.points {
+            file.write_all(&point.address.to_le_bytes())?;
+            file.write_all(&point.x.to_le_bytes())?;
+            file.write_all(&point.y.to_le_bytes())?;
+            file.write_all(&point.z.to_le_bytes())?;
+            file.write_all(&point.boost.to_le_bytes())?;
+        }
+        file.flush()
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn get(&self, address: i64) -> Option<&Point> {
+        self.addresses.get(&address).map(|&i| &self.points[i as usize])
+    }
+
+    /// Every point within `range` Ly of `point`, including itself.
+    pub fn within<'a>(&'a self, point: &'a Point, range: f64) -> impl Iterator<Item = &'a Point> + 'a {
+        self.within_indices(point, range).map(move |i| &self.points[i as usize])
+    }
+
+    fn within_indices<'a>(&'a self, point: &'a Point, range: f64) -> impl Iterator<Item = u32> + 'a {
+        let (x, y, z) = (point.x as f64, point.y as f64, point.z as f64);
+        let min = self.cell(x - range, y - range, z - range);
+        let max = self.cell(x + range, y + range, z + range);
+
+        (min.0..=max.0).flat_map(move |i| {
+            (min.1..=max.1).flat_map(move |j| {
+                (min.2..=max.2).filter_map(move |k| self.cells.get(&(i, j, k)))
+            })
+        })
+        .flatten()
+        .cloned()
+        .filter(move |&i| self.points[i as usize].distance(point) <= range)
+    }
+
+    /// Plots a route between two system addresses with unit cost jumps, like
+    /// `System::route_to`, returning the addresses along the way.
+    pub fn route(&self, start: i64, end: i64, range: f64)
+        -> Option<(Vec<i64>, OrderedFloat<f64>)>
+    {
+        let start = *self.addresses.get(&start)?;
+        let end = *self.addresses.get(&end)?;
+        let goal = self.points[end as usize];
+
+        let successors = |&i: &u32| {
+            let point = &self.points[i as usize];
+            self.within_indices(point, range)
+                .filter(move |&j| j != i)
+                .map(|j| (j, OrderedFloat(1.)))
+                .collect::<Vec<_>>()
+        };
+
+        let heuristic = |&i: &u32| {
+            OrderedFloat((self.points[i as usize].distance(&goal) / range).ceil())
+        };
+
+        let success = |&i: &u32| i == end;
+
+        astar(&start, successors, heuristic, success).map(|(path, cost)| {
+            (path.into_iter().map(|i| self.points[i as usize].address).collect(), cost)
+        })
+    }
+
+    /// Like `route`, but with supercharged jumps out of neutron stars and white dwarfs, like
+    /// `System::route_boosted`.
+    pub fn route_boosted(&self, start: i64, end: i64, range: f64)
+        -> Option<(Vec<i64>, OrderedFloat<f64>)>
+    {
+        let start = *self.addresses.get(&start)?;
+        let end = *self.addresses.get(&end)?;
+        let goal = self.points[end as usize];
+
+        let successors = |&i: &u32| {
+            let point = &self.points[i as usize];
+            self.within_indices(point, range * point.boost as f64)
+                .filter(move |&j| j != i)
+                .map(|j| (j, OrderedFloat(1.)))
+                .collect::<Vec<_>>()
+        };
+
+        let heuristic = |&i: &u32| {
+            let distance = self.points[i as usize].distance(&goal);
+            OrderedFloat((distance / (range * NEUTRON_BOOST)).ceil())
+        };
+
+        let success = |&i: &u32| i == end;
+
+        astar(&start, successors, heuristic, success).map(|(path, cost)| {
+            (path.into_iter().map(|i| self.points[i as usize].address).collect(), cost)
+        })
+    }
+}
+
+// Everything in a saved index before its points.
+#[derive(Debug, PartialEq)]
+struct Header {
+    cell_size: f64,
+    updated_at: Option<DateTime<Utc>>,
+    len: u64,
+}
+
+impl Header {
+    fn read<R: Read>(r: &mut R) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC || read_u32(r)? != VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a galos spatial index"));
+        }
+
+        let cell_size = read_f64(r)?;
+        // In microseconds, like Postgres.
+        let updated_at = match read_u64(r)? as i64 {
+            i64::MIN => None,
+            micros => {
+                let t = NaiveDateTime::from_timestamp(micros.div_euclid(1_000_000),
+                    micros.rem_euclid(1_000_000) as u32 * 1000);
+                Some(DateTime::<Utc>::from_utc(t, Utc))
+            },
+        };
+        let len = read_u64(r)?;
+        Ok(Header { cell_size, updated_at, len })
+    }
+
+    fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+        w.write_all(&self.cell_size.to_le_bytes())?;
+        let micros = self.updated_at.map_or(i64::MIN, |t| {
+            t.timestamp() * 1_000_000 + t.timestamp_subsec_micros() as i64
+        });
+        w.write_all(&micros.to_le_bytes())?;
+        w.write_all(&self.len.to_le_bytes())
+    }
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f32<R: Read>(r: &mut R) -> io::Result<f32> {
+    let mut bytes = [0; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(f32::from_le_bytes(bytes))
+}
+
+fn read_f64<R: Read>(r: &mut R) -> io::Result<f64> {
+    let mut bytes = [0; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use chrono::TimeZone;
+    use super::*;
+
+    fn point(address: i64, x: f32, y: f32, z: f32) -> Point {
+        Point { address, x, y, z, boost: 1. }
+    }
+
+    fn index() -> SpatialIndex {
+        SpatialIndex::new(10., vec![
+            point(1, 0., 0., 0.),
+            point(2, 9., 0., 0.),
+            point(3, 11., 0., 0.),
+            point(4, -5., -5., -5.),
+            point(5, 20., 0., 0.),
+            Point { boost: 4., ..point(6, 0., 0., 30.) },
+        ])
+    }
+
+    fn addresses<'a>(points: impl Iterator<Item = &'a Point>) -> Vec<i64> {
+        let mut addresses: Vec<i64> = points.map(|p| p.address).collect();
+        addresses.sort();
+        addresses
+    }
+
+    #[test]
+    fn within() {
+        let index = index();
+        let origin = *index.get(1).unwrap();
+        // Neighbours across cell boundaries, in every direction, are found.
+        assert_eq!(addresses(index.within(&origin, 10.)), vec![1, 2, 4]);
+        assert_eq!(addresses(index.within(&origin, 11.)), vec![1, 2, 3, 4]);
+        assert_eq!(addresses(index.within(&origin, 0.)), vec![1]);
+    }
+
+    #[test]
+    fn route() {
+        let index = index();
+        assert_eq!(index.route(1, 5, 10.), Some((vec![1, 2, 3, 5], OrderedFloat(3.))));
+        assert_eq!(index.route(1, 6, 11.), None);
+        assert_eq!(index.route(1, 404, 11.), None);
+        // Out of the neutron star, but not into it.
+        assert_eq!(index.route_boosted(6, 1, 10.), Some((vec![6, 1], OrderedFloat(1.))));
+        assert_eq!(index.route_boosted(1, 6, 10.), None);
+    }
+
+    #[test]
+    fn save_and_open() {
+        let path = env::temp_dir().join(format!("galos-spatial-{}.idx", std::process::id()));
+        let mut index = index();
+        index.updated_at = Some(Utc.ymd(3307, 5, 1).and_hms_nano(12, 30, 0, 123_456_000));
+        index.save(&path).unwrap();
+        let opened = SpatialIndex::open(&path);
+        fs::remove_file(&path).unwrap();
+        let opened = opened.unwrap();
+
+        assert_eq!(opened.cell_size, index.cell_size);
+        assert_eq!(opened.updated_at, index.updated_at);
+        assert_eq!(opened.points, index.points);
+        let origin = *opened.get(1).unwrap();
+        assert_eq!(addresses(opened.within(&origin, 11.)), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn header() {
+        let header = Header { cell_size: 10., updated_at: None, len: 3 };
+        let mut bytes = Vec::new();
+        header.write(&mut bytes).unwrap();
+        assert_eq!(Header::read(&mut bytes.as_slice()).unwrap(), header);
+
+        bytes[0] = b'X';
+        assert!(Header::read(&mut bytes.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,240 @@
+use std::str::FromStr;
+use chrono::{DateTime, Utc};
+use elite_journal::{prelude::*, station::Station as JournalStation};
+use crate::{Error, Database};
+use crate::factions::Faction;
+
+#[derive(sqlx::Type, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[sqlx(type_name = "landingpad")]
+pub enum LandingPad {
+    Small,
+    Medium,
+    Large,
+}
+
+impl FromStr for LandingPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "S" | "SMALL" => Ok(Self::Small),
+            "M" | "MEDIUM" => Ok(Self::Medium),
+            "L" | "LARGE" => Ok(Self::Large),
+            _ => Err("invalid landing pad".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Station {
+    pub market_id: u64,
+    pub system_address: u64,
+    pub name: String,
+    pub ty: Option<String>,
+    /// Light seconds from the arrival star.
+    pub distance_to_arrival: Option<f64>,
+    /// The largest landing pad.
+    pub landing_pad: Option<LandingPad>,
+    pub services: Vec<String>,
+    pub faction: Option<String>,
+    pub government: Option<Government>,
+    pub allegiance: Option<Allegiance>,
+    pub primary_economy: Option<Economy>,
+    pub secondary_economy: Option<Economy>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Station {
+    /// Inserts or updates the station, unless the stored station is newer.
+    ///
+    /// Fleet carriers keep their market ID as they jump, so this also moves a carrier to
+    /// `system_address`.
+    pub async fn create(db: &Database, station: &Station) -> Result<(), Error> {
+        let faction_id = match &station.faction {
+            Some(name) => Some(Faction::create(db, name).await?.id),
+            None => None,
+        };
+
+        sqlx::query!(
+            r#"
+            INSERT INTO stations
+                (market_id,
+                 system_address,
+                 name,
+                 type,
+                 distance_to_arrival,
+                 landing_pad,
+                 services,
+                 faction_id,
+                 government,
+                 allegiance,
+                 primary_economy,
+                 secondary_economy,
+                 updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+            ON CONFLICT (market_id)
+            DO UPDATE SET
+                system_address = $2,
+                name = $3,
+                type = COALESCE($4, stations.type),
+                distance_to_arrival = COALESCE($5, stations.distance_to_arrival),
+                landing_pad = COALESCE($6, stations.landing_pad),
+                services = $7,
+                faction_id = COALESCE($8, stations.faction_id),
+                government = COALESCE($9, stations.government),
+                allegiance = COALESCE($10, stations.allegiance),
+                primary_economy = COALESCE($11, stations.primary_economy),
+                secondary_economy = COALESCE($12, stations.secondary_economy),
+                updated_at = $13
+            WHERE stations.updated_at < $13
+            "#,
+            station.market_id as i64,
+            station.system_address as i64,
+            station.name,
+            station.ty,
+            station.distance_to_arrival,
+            station.landing_pad as _,
+            &station.services[..],
+            faction_id,
+            station.government as _,
+            station.allegiance as _,
+            station.primary_economy as _,
+            station.secondary_economy as _,
+            station.updated_at.naive_utc())
+            .execute(&db.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn from_journal(
+        db: &Database,
+        system_address: u64,
+        station: &JournalStation,
+        timestamp: DateTime<Utc>)
+        -> Result<Self, Error>
+    {
+        let mut economies = station.economies.clone();
+        economies.sort_by(|a, b| b.proportion.partial_cmp(&a.proportion).expect("comparable"));
+
+        let landing_pad = station.landing_pads.as_ref().and_then(|pads| {
+            if pads.large > 0 {
+                Some(LandingPad::Large)
+            } else if pads.medium > 0 {
+                Some(LandingPad::Medium)
+            } else if pads.small > 0 {
+                Some(LandingPad::Small)
+            } else {
+                None
+            }
+        });
+
+        let station = Station {
+            market_id: station.market_id,
+            system_address,
+            name: station.name.clone(),
+            ty: station.ty.clone(),
+            distance_to_arrival: station.dist_from_star_ls,
+            landing_pad,
+            services: station.services.clone(),
+            faction: station.faction.as_ref().map(|f| f.name.clone()),
+            government: station.government,
+            allegiance: station.allegiance,
+            primary_economy: economies.get(0).map(|e| e.name).or(station.economy),
+            secondary_economy: economies.get(1).map(|e| e.name),
+            updated_at: timestamp,
+        };
+
+        Station::create(db, &station).await?;
+        Ok(station)
+    }
+
+    pub async fn fetch(db: &Database, market_id: u64) -> Result<Self, Error> {
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                market_id,
+                system_address,
+                stations.name,
+                type AS ty,
+                distance_to_arrival,
+                landing_pad AS "landing_pad: LandingPad",
+                services,
+                factions.name AS "faction?",
+                government AS "government: Government",
+                allegiance AS "allegiance: Allegiance",
+                primary_economy AS "primary_economy: Economy",
+                secondary_economy AS "secondary_economy: Economy",
+                updated_at
+            FROM stations
+            LEFT JOIN factions ON faction_id = factions.id
+            WHERE market_id = $1
+            "#, market_id as i64)
+            .fetch_one(&db.pool)
+            .await?;
+
+        Ok(Station {
+            market_id: row.market_id as u64,
+            system_address: row.system_address as u64,
+            name: row.name,
+            ty: row.ty,
+            distance_to_arrival: row.distance_to_arrival,
+            landing_pad: row.landing_pad,
+            services: row.services,
+            faction: row.faction,
+            government: row.government,
+            allegiance: row.allegiance,
+            primary_economy: row.primary_economy,
+            secondary_economy: row.secondary_economy,
+            updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
+        })
+    }
+
+    pub async fn fetch_all(db: &Database, system_address: u64) -> Result<Vec<Self>, Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                market_id,
+                system_address,
+                stations.name,
+                type AS ty,
+                distance_to_arrival,
+                landing_pad AS "landing_pad: LandingPad",
+                services,
+                factions.name AS "faction?",
+                government AS "government: Government",
+                allegiance AS "allegiance: Allegiance",
+                primary_economy AS "primary_economy: Economy",
+                secondary_economy AS "secondary_economy: Economy",
+                updated_at
+            FROM stations
+            LEFT JOIN factions ON faction_id = factions.id
+            WHERE system_address = $1
+            ORDER BY distance_to_arrival
+            "#, system_address as i64)
+            .fetch_all(&db.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| {
+            Station {
+                market_id: row.market_id as u64,
+                system_address: row.system_address as u64,
+                name: row.name,
+                ty: row.ty,
+                distance_to_arrival: row.distance_to_arrival,
+                landing_pad: row.landing_pad,
+                services: row.services,
+                faction: row.faction,
+                government: row.government,
+                allegiance: row.allegiance,
+                primary_economy: row.primary_economy,
+                secondary_economy: row.secondary_economy,
+                updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
+            }
+        }).collect())
+    }
+
+    pub fn is_carrier(&self) -> bool {
+        self.ty.as_deref() == Some("FleetCarrier")
+    }
+}
@@ -0,0 +1,76 @@
+//! The names systems are known by, since a system may have several and a name may belong to
+//! several systems.
+
+use crate::{Error, Database};
+
+#[derive(sqlx::Type, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "systemnamekind")]
+pub enum SystemNameKind {
+    /// Generated from the system's sector and position, like "Synuefe EN-H d11-96".
+    Procedural,
+    /// From a star catalogue, or given by hand, like "HIP 8396" or "Sol".
+    Catalogue,
+    /// A name given to a system which is already known by another.
+    Renamed,
+    /// A name the system is no longer known by.
+    Historical,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemName {
+    pub address: i64,
+    pub name: String,
+    pub kind: SystemNameKind,
+}
+
+impl SystemName {
+    /// Adds a name to an existing system. Without a kind, a name which isn't the system's
+    /// primary name is `Renamed`, otherwise it's `Procedural` or `Catalogue` by its form.
+    pub async fn create(db: &Database, address: u64, name: &str, kind: Option<SystemNameKind>)
+        -> Result<(), Error>
+    {
+        sqlx::query!(
+            r#"
+            INSERT INTO system_names (address, name, kind)
+            SELECT
+                address,
+                UPPER($2),
+                COALESCE($3,
+                    CASE WHEN name = UPPER($2)
+                        THEN system_name_kind($2)
+                        ELSE 'Renamed'
+                    END)
+            FROM systems
+            WHERE address = $1
+            ON CONFLICT (address, name)
+            DO UPDATE SET kind = COALESCE($3, system_names.kind)
+            "#,
+            address as i64,
+            name,
+            kind as _)
+            .execute(&db.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn fetch_all(db: &Database, address: u64) -> Result<Vec<Self>, Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT address, name, kind AS "kind: SystemNameKind"
+            FROM system_names
+            WHERE address = $1
+            ORDER BY kind, name
+            "#, address as i64)
+            .fetch_all(&db.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| {
+            SystemName {
+                address: row.address,
+                name: row.name,
+                kind: row.kind,
+            }
+        }).collect())
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
 use std::str::FromStr;
 use async_std::task;
 use chrono::{DateTime, Utc};
@@ -7,11 +9,14 @@ use ordered_float::OrderedFloat;
 use elite_journal::{prelude::*, system::System as JournalSystem};
 use crate::{Error, Database};
 use crate::factions::{Faction, SystemFaction, Conflict};
+use crate::system_names::SystemName;
+use crate::collisions::Collision;
+use crate::spatial::SpatialIndex;
 
 #[derive(Debug, Clone)]
 pub struct System {
     pub address: i64,
-    // TODO: We need to support multiple names
+    /// The name the system was first seen with, see `SystemName` for all of its names.
     pub name: String,
     pub position: Coordinate,
     pub population: u64,
@@ -20,6 +25,9 @@ pub struct System {
     pub allegiance: Option<Allegiance>,
     pub primary_economy: Option<Economy>,
     pub secondary_economy: Option<Economy>,
+    pub primary_star_class: Option<String>,
+    /// The number of bodies, once the system has been honked.
+    pub body_count: Option<u32>,
 
     // TODO: Find an elegent way to represent this.
     // & = foreign key = belongs_to
@@ -29,6 +37,19 @@ pub struct System {
     pub updated_at: DateTime<Utc>,
 }
 
+/// A system's state as of `updated_at`, see `System::history`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemHistory {
+    pub address: i64,
+    pub population: Option<u64>,
+    pub security: Option<Security>,
+    pub government: Option<Government>,
+    pub allegiance: Option<Allegiance>,
+    pub primary_economy: Option<Economy>,
+    pub secondary_economy: Option<Economy>,
+    pub updated_at: DateTime<Utc>,
+}
+
 impl System {
     pub async fn create(db: &Database,
         address: u64,
@@ -43,6 +64,10 @@ impl System {
         updated_at: DateTime<Utc>)
         -> Result<(), Error>
     {
+        if Collision::detect(db, address, name, position, updated_at).await? {
+            return Ok(());
+        }
+
         sqlx::query!(
             r#"
             INSERT INTO systems
@@ -64,7 +89,8 @@ impl System {
                 government = $6,
                 allegiance = $7,
                 primary_economy = $8,
-                secondary_economy = $9
+                secondary_economy = $9,
+                updated_at = $10
             WHERE systems.updated_at < $10
             "#,
             address as i64,
@@ -80,6 +106,8 @@ impl System {
             .execute(&db.pool)
             .await?;
 
+        SystemName::create(db, address, name, None).await?;
+
         Ok(())
     }
 
@@ -91,7 +119,17 @@ impl System {
             y: system.pos.y,
             z: system.pos.z,
         };
-        // TODO: Conflicts on pos need to do something else.
+        // The factions are kept with the collision. Conflicts are skipped along with the
+        // system, since they're between factions of an address we don't store.
+        if Collision::detect(db, system.address, &system.name, position, timestamp).await? {
+            for faction in &system.factions {
+                let faction_id = Faction::create(db, &faction.name).await?.id;
+                Collision::add_faction(db,
+                    system.address, faction_id as u32, faction, timestamp).await?;
+            }
+            return Ok(());
+        }
+
         sqlx::query!(
             r#"
             INSERT INTO systems
@@ -113,7 +151,9 @@ impl System {
                 government = $6,
                 allegiance = $7,
                 primary_economy = $8,
-                secondary_economy = $9
+                secondary_economy = $9,
+                updated_at = $10
+            WHERE systems.updated_at < $10
             "#, system.address as i64,
                 system.name,
                 wkb::Encode(position) as _,
@@ -127,6 +167,8 @@ impl System {
             .execute(&db.pool)
             .await?;
 
+        SystemName::create(db, system.address, &system.name, None).await?;
+
         for faction in &system.factions {
             let faction_id = Faction::create(db, &faction.name).await?.id;
             SystemFaction::from_journal(db,
@@ -141,6 +183,38 @@ impl System {
         Ok(())
     }
 
+    pub async fn set_primary_star_class(db: &Database, address: u64, star_class: &str)
+        -> Result<(), Error>
+    {
+        sqlx::query!(
+            r#"
+            UPDATE systems
+            SET primary_star_class = $2
+            WHERE address = $1
+            "#, address as i64, star_class)
+            .execute(&db.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Records the number of bodies and other signals found by a discovery scan.
+    pub async fn set_body_count(db: &Database, address: u64, body_count: u32, non_body_count: u32)
+        -> Result<(), Error>
+    {
+        sqlx::query!(
+            r#"
+            UPDATE systems
+            SET body_count = $2,
+                non_body_count = $3
+            WHERE address = $1
+            "#, address as i64, body_count as i32, non_body_count as i32)
+            .execute(&db.pool)
+            .await?;
+
+        Ok(())
+    }
+
     pub async fn fetch(db: &Database, address: i64) -> Result<Self, Error> {
         let row = sqlx::query!(
             r#"
@@ -154,6 +228,8 @@ impl System {
                 allegiance as "allegiance: Allegiance",
                 primary_economy as "primary_economy: Economy",
                 secondary_economy as "secondary_economy: Economy",
+                primary_star_class,
+                body_count,
                 updated_at
             FROM systems
             WHERE address = $1
@@ -171,13 +247,16 @@ impl System {
             allegiance: row.allegiance,
             primary_economy: row.primary_economy,
             secondary_economy: row.secondary_economy,
+            primary_star_class: row.primary_star_class,
+            body_count: row.body_count.map(|n| n as u32),
             updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
         })
     }
 
-    // NOTE: Assumes systems are unique by name, which is currently untrue.
-    pub async fn fetch_by_name(db: &Database, name: &str) -> Result<Self, Error> {
-        let row = sqlx::query!(
+    /// Fetches many systems at once, in the same order as `addresses`. Unknown addresses are
+    /// skipped.
+    pub async fn fetch_many(db: &Database, addresses: &[i64]) -> Result<Vec<Self>, Error> {
+        let rows = sqlx::query!(
             r#"
             SELECT
                 address,
@@ -189,25 +268,146 @@ impl System {
                 allegiance as "allegiance: Allegiance",
                 primary_economy as "primary_economy: Economy",
                 secondary_economy as "secondary_economy: Economy",
+                primary_star_class,
+                body_count,
                 updated_at
             FROM systems
-            WHERE name = $1
+            WHERE address = ANY($1)
+            "#, addresses)
+            .fetch_all(&db.pool)
+            .await?;
+
+        let mut systems: HashMap<i64, System> = rows.into_iter().map(|row| {
+            (row.address, System {
+                address: row.address,
+                name: row.name,
+                position: row.position.geometry.expect("not null or invalid"),
+                population: row.population.map(|n| n as u64).unwrap_or(0),
+                security: row.security,
+                government: row.government,
+                allegiance: row.allegiance,
+                primary_economy: row.primary_economy,
+                secondary_economy: row.secondary_economy,
+                primary_star_class: row.primary_star_class,
+                body_count: row.body_count.map(|n| n as u32),
+                updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
+            })
+        }).collect();
+
+        Ok(addresses.iter().filter_map(|a| systems.remove(a)).collect())
+    }
+
+    /// Every state the system has been seen in, oldest first. Each entry lasts until the next,
+    /// and the last is the system's current state.
+    pub async fn history(db: &Database, address: u64) -> Result<Vec<SystemHistory>, Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                address,
+                population,
+                security as "security: Security",
+                government as "government: Government",
+                allegiance as "allegiance: Allegiance",
+                primary_economy as "primary_economy: Economy",
+                secondary_economy as "secondary_economy: Economy",
+                updated_at
+            FROM system_history
+            WHERE address = $1
+            ORDER BY updated_at
+            "#, address as i64)
+            .fetch_all(&db.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| {
+            SystemHistory {
+                address: row.address,
+                population: row.population.map(|n| n as u64),
+                security: row.security,
+                government: row.government,
+                allegiance: row.allegiance,
+                primary_economy: row.primary_economy,
+                secondary_economy: row.secondary_economy,
+                updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
+            }
+        }).collect())
+    }
+
+    /// Up to `limit` systems whose current state isn't their latest history entry. There are
+    /// none while the `system_changes` trigger is working.
+    pub async fn fetch_missing_history(db: &Database, limit: i64) -> Result<Vec<i64>, Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT s.address
+            FROM systems s
+            LEFT JOIN LATERAL (
+                SELECT *
+                FROM system_history h
+                WHERE h.address = s.address
+                ORDER BY h.updated_at DESC
+                LIMIT 1
+            ) h ON true
+            WHERE h.address IS NULL
+               OR (s.population,
+                   s.security,
+                   s.government,
+                   s.allegiance,
+                   s.primary_economy,
+                   s.secondary_economy)
+                  IS DISTINCT FROM
+                  (h.population,
+                   h.security,
+                   h.government,
+                   h.allegiance,
+                   h.primary_economy,
+                   h.secondary_economy)
+            LIMIT $1
+            "#, limit)
+            .fetch_all(&db.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| row.address).collect())
+    }
+
+    /// Every system known by the name, since names aren't unique.
+    pub async fn fetch_by_name(db: &Database, name: &str) -> Result<Vec<Self>, Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                address,
+                name,
+                position AS "position!: wkb::Decode<Coordinate>",
+                population,
+                security as "security: Security",
+                government as "government: Government",
+                allegiance as "allegiance: Allegiance",
+                primary_economy as "primary_economy: Economy",
+                secondary_economy as "secondary_economy: Economy",
+                primary_star_class,
+                body_count,
+                updated_at
+            FROM systems
+            WHERE address IN (SELECT address FROM system_names WHERE name = $1)
+            ORDER BY address
             "#, name.to_uppercase())
-            .fetch_one(&db.pool)
+            .fetch_all(&db.pool)
             .await?;
 
-        Ok(System {
-            address: row.address,
-            name: row.name,
-            position: row.position.geometry.expect("not null or invalid"),
-            population: row.population.map(|n| n as u64).unwrap_or(0),
-            security: row.security,
-            government: row.government,
-            allegiance: row.allegiance,
-            primary_economy: row.primary_economy,
-            secondary_economy: row.secondary_economy,
-            updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
-        })
+        Ok(rows.into_iter().map(|row| {
+            System {
+                address: row.address,
+                name: row.name,
+                position: row.position.geometry.expect("not null or invalid"),
+                population: row.population.map(|n| n as u64).unwrap_or(0),
+                security: row.security,
+                government: row.government,
+                allegiance: row.allegiance,
+                primary_economy: row.primary_economy,
+                secondary_economy: row.secondary_economy,
+                primary_star_class: row.primary_star_class,
+                body_count: row.body_count.map(|n| n as u32),
+                updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
+            }
+        }).collect())
     }
 
     pub async fn fetch_like_name(db: &Database, name: &str) -> Result<Vec<Self>, Error> {
@@ -223,9 +423,11 @@ impl System {
                 allegiance as "allegiance: Allegiance",
                 primary_economy as "primary_economy: Economy",
                 secondary_economy as "secondary_economy: Economy",
+                primary_star_class,
+                body_count,
                 updated_at
             FROM systems
-            WHERE name ILIKE $1
+            WHERE address IN (SELECT address FROM system_names WHERE name ILIKE $1)
             ORDER BY name
             "#, name)
             .fetch_all(&db.pool)
@@ -242,11 +444,14 @@ impl System {
                 allegiance: row.allegiance,
                 primary_economy: row.primary_economy,
                 secondary_economy: row.secondary_economy,
+                primary_star_class: row.primary_star_class,
+                body_count: row.body_count.map(|n| n as u32),
                 updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
             }
         }).collect())
     }
 
+    /// Systems within range of every system known by the name.
     pub async fn fetch_in_range_by_name(db: &Database, range: f64, name: &str) -> Result<Vec<Self>, Error> {
         let rows = sqlx::query!(
             r#"
@@ -260,10 +465,12 @@ impl System {
                 s1.allegiance as "allegiance: Allegiance",
                 s1.primary_economy as "primary_economy: Economy",
                 s1.secondary_economy as "secondary_economy: Economy",
+                s1.primary_star_class,
+                s1.body_count,
                 s1.updated_at
             FROM systems s1
             FULL JOIN systems s2 ON ST_3DDWithin(s1.position, s2.position, $2)
-            WHERE s2.name = $1
+            WHERE s2.address IN (SELECT address FROM system_names WHERE name = $1)
             ORDER BY ST_3DDistance(s1.position, s2.position)
             "#, name.to_uppercase(), range)
             .fetch_all(&db.pool)
@@ -280,6 +487,8 @@ impl System {
                 allegiance: row.allegiance,
                 primary_economy: row.primary_economy,
                 secondary_economy: row.secondary_economy,
+                primary_star_class: row.primary_star_class,
+                body_count: row.body_count.map(|n| n as u32),
                 updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
             }
         }).collect())
@@ -298,10 +507,12 @@ impl System {
                 s1.allegiance as "allegiance: Allegiance",
                 s1.primary_economy as "primary_economy: Economy",
                 s1.secondary_economy as "secondary_economy: Economy",
+                s1.primary_star_class,
+                s1.body_count,
                 s1.updated_at
             FROM systems s1
             FULL JOIN systems s2 ON ST_3DDWithin(s1.position, s2.position, $2)
-            WHERE s2.name ILIKE $1
+            WHERE s2.address IN (SELECT address FROM system_names WHERE name ILIKE $1)
             ORDER BY ST_3DDistance(s1.position, s2.position)
             "#, name, range)
             .fetch_all(&db.pool)
@@ -318,6 +529,8 @@ impl System {
                 allegiance: row.allegiance,
                 primary_economy: row.primary_economy,
                 secondary_economy: row.secondary_economy,
+                primary_star_class: row.primary_star_class,
+                body_count: row.body_count.map(|n| n as u32),
                 updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
             }
         }).collect())
@@ -337,6 +550,8 @@ impl System {
                     allegiance as "allegiance: Allegiance",
                     primary_economy as "primary_economy: Economy",
                     secondary_economy as "secondary_economy: Economy",
+                    primary_star_class,
+                    body_count,
                     updated_at
                 FROM systems
                 WHERE ST_3DDWithin(position, $1, $2);
@@ -356,6 +571,8 @@ impl System {
                 allegiance: row.allegiance,
                 primary_economy: row.primary_economy,
                 secondary_economy: row.secondary_economy,
+                primary_star_class: row.primary_star_class,
+                body_count: row.body_count.map(|n| n as u32),
                 updated_at: DateTime::<Utc>::from_utc(row.updated_at, Utc),
             }
         }).collect()
@@ -387,6 +604,197 @@ impl System {
 
         Ok(astar(self, successors, heuristic, success))
     }
+
+    /// Like `route_to`, but searches the in-memory `index` instead of querying the database
+    /// for each system's neighbors.
+    pub fn route_to_indexed(&self, db: &Database, index: &SpatialIndex, end: &System, range: f64)
+        -> Result<Option<(Vec<Self>, OrderedFloat<f64>)>, Error>
+    {
+        match index.route(self.address, end.address, range) {
+            Some((addresses, cost)) => {
+                let route = task::block_on(System::fetch_many(db, &addresses))?;
+                Ok(Some((route, cost)))
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Plots a route where jumping out of a neutron star or white dwarf is supercharged, see
+    /// `jump_boost`. Each jump costs the same, boosted or not.
+    pub fn route_boosted(&self, db: &Database, end: &System, range: f64)
+        -> Result<Option<(Vec<Self>, OrderedFloat<f64>)>, Error>
+    {
+        let successors = |s: &System| {
+            s.neighbors(db, range * s.jump_boost()).into_iter().map(|s| (s, OrderedFloat(1.)))
+        };
+
+        // Assumes every jump could be a neutron boosted one, so this heuristic never
+        // overestimates, at the cost of exploring more systems.
+        let heuristic = |s: &System| {
+            OrderedFloat((s.distance(end) / (range * NEUTRON_BOOST)).ceil())
+        };
+
+        let success = |s: &System| s == end;
+
+        Ok(astar(self, successors, heuristic, success))
+    }
+
+    /// Like `route_boosted`, but searches the in-memory `index`.
+    pub fn route_boosted_indexed(&self, db: &Database, index: &SpatialIndex, end: &System, range: f64)
+        -> Result<Option<(Vec<Self>, OrderedFloat<f64>)>, Error>
+    {
+        match index.route_boosted(self.address, end.address, range) {
+            Some((addresses, cost)) => {
+                let route = task::block_on(System::fetch_many(db, &addresses))?;
+                Ok(Some((route, cost)))
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Plots a route where each jump costs the fuel it uses, refueling to a full tank at each
+    /// scoopable star along the way. The ship leaves this system with a full tank.
+    ///
+    /// Since fuel use grows faster than distance, the cheapest route is often many short
+    /// jumps, so like `route_to` the heuristic trades optimality for speed.
+    pub fn route_with_fuel(&self, db: &Database, end: &System, ship: &Ship)
+        -> Result<Option<(Vec<Jump>, OrderedFloat<f64>)>, Error>
+    {
+        let range = ship.jump_range();
+        let start = Fuelled {
+            system: self.clone(),
+            fuel: OrderedFloat(ship.tank),
+        };
+
+        let successors = |f: &Fuelled| {
+            let fuel = f.fuel.into_inner();
+            f.system.neighbors(db, range).into_iter().filter_map(move |s| {
+                let cost = ship.fuel_cost(f.system.distance(&s));
+                if s == f.system || cost > fuel {
+                    return None;
+                }
+
+                let remaining = if s.is_scoopable() { ship.tank } else { fuel - cost };
+                let next = Fuelled {
+                    system: s,
+                    fuel: OrderedFloat((remaining * 100.).floor() / 100.),
+                };
+                Some((next, OrderedFloat(cost)))
+            }).collect::<Vec<_>>()
+        };
+
+        let heuristic = |f: &Fuelled| {
+            let distance = f.system.distance(end);
+            let jumps = (distance / range).ceil();
+            if jumps > 0. {
+                OrderedFloat(jumps * ship.fuel_cost(distance / jumps))
+            } else {
+                OrderedFloat(0.)
+            }
+        };
+
+        let success = |f: &Fuelled| &f.system == end;
+
+        Ok(astar(&start, successors, heuristic, success).map(|(path, cost)| {
+            let mut jumps = Vec::with_capacity(path.len());
+            let mut fuel = ship.tank;
+            for (i, f) in path.into_iter().enumerate() {
+                let used = jumps.last().map_or(0., |j: &Jump| ship.fuel_cost(j.system.distance(&f.system)));
+                let refuel = i > 0 && f.fuel.into_inner() > fuel - used;
+                fuel = f.fuel.into_inner();
+                jumps.push(Jump { system: f.system, fuel: used, refuel });
+            }
+            (jumps, cost)
+        }))
+    }
+
+    /// The jump range multiplier for a supercharged jump out of this system, 1 when its
+    /// primary star is unknown or can't supercharge the FSD.
+    pub fn jump_boost(&self) -> f64 {
+        self.primary_star_class.as_deref().map_or(1., jump_boost)
+    }
+
+    /// Returns true when this system's primary star is known to be scoopable (KGBFOAM).
+    pub fn is_scoopable(&self) -> bool {
+        match &self.primary_star_class {
+            Some(class) => is_scoopable(class),
+            None => false,
+        }
+    }
+}
+
+// Search state for `System::route_with_fuel`, fuel is kept to the hundredth of a ton.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Fuelled {
+    system: System,
+    fuel: OrderedFloat<f64>,
+}
+
+/// A single stop on a fuel aware route.
+#[derive(Debug, Clone)]
+pub struct Jump {
+    pub system: System,
+    /// Fuel used jumping to this system, in tons.
+    pub fuel: f64,
+    /// Whether the tank is topped up from the star here.
+    pub refuel: bool,
+}
+
+/// The parts of a ship which determine how far, and how cheaply, it jumps.
+#[derive(Debug, Clone, Copy)]
+pub struct Ship {
+    /// Total mass of the ship, including fuel and cargo, in tons.
+    pub mass: f64,
+    /// The optimized mass of the frame shift drive, in tons.
+    pub optimal_mass: f64,
+    /// The frame shift drive's size, one of `Ship::FSD_SIZES`.
+    pub size: u8,
+    pub class: ModuleClass,
+    /// Fuel tank capacity, in tons.
+    pub tank: f64,
+}
+
+impl Ship {
+    /// The sizes of frame shift drive which can be fitted.
+    pub const FSD_SIZES: RangeInclusive<u8> = 2..=7;
+
+    pub fn fuel_cost(&self, distance: f64) -> f64 {
+        fuel_cost(distance, self.mass, self.optimal_mass, self.size, self.class)
+    }
+
+    /// The most fuel the frame shift drive will use for a single jump, in tons.
+    pub fn max_fuel_per_jump(&self) -> f64 {
+        let max = match (self.size, self.class) {
+            (2, ModuleClass::A) => 0.9,
+            (2, ModuleClass::B) => 0.8,
+            (2, _) => 0.6,
+            (3, ModuleClass::A) => 1.8,
+            (3, ModuleClass::B) => 1.5,
+            (3, _) => 1.2,
+            (4, ModuleClass::A) => 3.0,
+            (4, ModuleClass::B) => 2.5,
+            (4, _) => 2.0,
+            (5, ModuleClass::A) => 5.0,
+            (5, ModuleClass::B) => 4.1,
+            (5, _) => 3.3,
+            (6, ModuleClass::A) => 8.0,
+            (6, ModuleClass::B) => 6.6,
+            (6, _) => 5.3,
+            (7, ModuleClass::A) => 12.8,
+            (7, ModuleClass::B) => 10.6,
+            (7, _) => 8.5,
+            (size, _) => panic!("no FSD of size {}, see `Ship::FSD_SIZES`", size),
+        };
+
+        max.min(self.tank)
+    }
+
+    /// The longest single jump this ship can make, in light years.
+    pub fn jump_range(&self) -> f64 {
+        let l = self.class.rating_constant();
+        let p = size_constant(self.size);
+        self.optimal_mass / self.mass * (self.max_fuel_per_jump() * 1000. / l).powf(1. / p)
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -413,17 +821,20 @@ impl FromStr for ModuleClass {
     }
 }
 
-// https://www.reddit.com/r/EliteDangerous/comments/30nx4u/the_hyperspace_fuel_equation_documented
-pub fn _fuel_cost(distance: f64, mass: f64, optimal_mass: f64, size: u8, class: ModuleClass) -> f64 {
-    let l = match class {
-        ModuleClass::A => 12.,
-        ModuleClass::B => 10.,
-        ModuleClass::C => 8.,
-        ModuleClass::D => 10.,
-        ModuleClass::E => 11.,
-    };
-
-    let p = match size {
+impl ModuleClass {
+    fn rating_constant(&self) -> f64 {
+        match self {
+            ModuleClass::A => 12.,
+            ModuleClass::B => 10.,
+            ModuleClass::C => 8.,
+            ModuleClass::D => 10.,
+            ModuleClass::E => 11.,
+        }
+    }
+}
+
+fn size_constant(size: u8) -> f64 {
+    match size {
         2 => 2.,
         3 => 2.15,
         4 => 2.3,
@@ -432,11 +843,40 @@ pub fn _fuel_cost(distance: f64, mass: f64, optimal_mass: f64, size: u8, class:
         7 => 2.75,
         8 => 2.9,
         _ => panic!("bad size"),
-    };
+    }
+}
 
+// https://www.reddit.com/r/EliteDangerous/comments/30nx4u/the_hyperspace_fuel_equation_documented
+pub fn fuel_cost(distance: f64, mass: f64, optimal_mass: f64, size: u8, class: ModuleClass) -> f64 {
+    let l = class.rating_constant();
+    let p = size_constant(size);
     l * 0.001 * (distance * mass / optimal_mass).powf(p)
 }
 
+/// Main sequence and giant stars of class K, G, B, F, O, A or M can be fuel scooped. Journal
+/// star classes look like `K`, `M_RedGiant` or `A_BlueWhiteSuperGiant`.
+pub fn is_scoopable(star_class: &str) -> bool {
+    match star_class.split('_').next() {
+        Some("K") | Some("G") | Some("B") | Some("F") | Some("O") | Some("A") | Some("M") => true,
+        _ => false,
+    }
+}
+
+pub const NEUTRON_BOOST: f64 = 4.;
+pub const WHITE_DWARF_BOOST: f64 = 1.5;
+
+/// Neutron stars (`N`) quadruple the jump range, and white dwarfs (`D`, `DA`, `DAB`, ...)
+/// increase it by half.
+pub fn jump_boost(star_class: &str) -> f64 {
+    if star_class == "N" {
+        NEUTRON_BOOST
+    } else if star_class.starts_with('D') {
+        WHITE_DWARF_BOOST
+    } else {
+        1.
+    }
+}
+
 impl Eq for System {}
 impl PartialEq for System {
     fn eq(&self, other: &Self) -> bool {
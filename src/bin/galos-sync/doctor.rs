@@ -0,0 +1,74 @@
+use async_std::task;
+use structopt::StructOpt;
+use galos_db::{Database, collisions::Collision, systems::System};
+use crate::Run;
+
+#[derive(StructOpt, Debug)]
+pub struct Cli {}
+
+// How many systems without history to list.
+const HISTORY_LIMIT: i64 = 20;
+
+impl Run for Cli {
+    fn run(&self, db: &Database) {
+        collisions(db);
+        history(db);
+    }
+}
+
+fn collisions(db: &Database) {
+    let collisions = match task::block_on(Collision::fetch_all(db)) {
+        Ok(collisions) => collisions,
+        Err(err) => panic!("{}", err),
+    };
+
+    if collisions.is_empty() {
+        println!("[DOCTOR] No position collisions");
+        return;
+    }
+
+    println!("[DOCTOR] {} systems collide with another system's position, and weren't stored:",
+        collisions.len());
+    for c in &collisions {
+        println!("{} ({}) at {:.5}, {:.5}, {:.5} collides with {} ({}), seen {} times from {} to {}",
+            c.name,
+            c.address,
+            c.position.x,
+            c.position.y,
+            c.position.z,
+            c.existing_name,
+            c.existing_address,
+            c.count,
+            c.first_seen_at,
+            c.last_seen_at);
+
+        match task::block_on(c.factions(db)) {
+            Ok(factions) => for f in factions {
+                println!("    {} {:.2}% as of {}", f.faction, f.influence * 100., f.updated_at);
+            },
+            Err(err) => println!("[DOCTOR ERROR] {}", err),
+        }
+    }
+}
+
+fn history(db: &Database) {
+    let result = task::block_on(async {
+        let addresses = System::fetch_missing_history(db, HISTORY_LIMIT).await?;
+        System::fetch_many(db, &addresses).await
+    });
+    let systems = match result {
+        Ok(systems) => systems,
+        Err(err) => return println!("[DOCTOR ERROR] {}", err),
+    };
+
+    if systems.is_empty() {
+        println!("[DOCTOR] System history is up to date");
+        return;
+    }
+
+    println!("[DOCTOR] These systems' history is missing their current state, check the \
+              system_changes trigger:");
+    for system in &systems {
+        println!("{} ({})", system.name, system.address);
+    }
+}
@@ -0,0 +1,44 @@
+//! Reading of the large JSON dumps published by EDSM and Spansh.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use indicatif::{ProgressBar, ProgressStyle};
+use flate2::read::GzDecoder;
+use galos_db::Error;
+
+/// Opens a dump of a JSON array with one object per line, optionally gzipped, returning a
+/// progress bar and an iterator over each object's JSON.
+///
+/// Progress is counted in (compressed) bytes read, since that's the size we know up front,
+/// and only one line is held in memory at a time.
+pub fn lines(path: &str) -> Result<(ProgressBar, impl Iterator<Item = Result<String, Error>>), Error> {
+    let file = File::open(path)?;
+
+    let bar = ProgressBar::new(file.metadata()?.len());
+    bar.set_style(ProgressStyle::default_bar()
+        .template("[{elapsed_precise}/{eta_precise}] {bar:40} {pos:>7}/{len:7} ({percent}%) {msg}")
+        .progress_chars("##-"));
+
+    let reader = bar.wrap_read(file);
+    let reader: Box<dyn Read> = if path.ends_with(".gz") {
+        Box::new(GzDecoder::new(reader))
+    } else {
+        Box::new(reader)
+    };
+
+    let lines = BufReader::new(reader).lines().filter_map(|line| {
+        match line {
+            Ok(line) => {
+                let json = line.trim().trim_end_matches(',');
+                if json.is_empty() || json == "[" || json == "]" {
+                    None
+                } else {
+                    Some(Ok(json.to_string()))
+                }
+            },
+            Err(err) => Some(Err(err.into())),
+        }
+    });
+
+    Ok((bar, lines))
+}
@@ -2,8 +2,11 @@ use async_std::task;
 use structopt::StructOpt;
 use indicatif::{ProgressBar, ProgressStyle};
 use elite_journal::system::Coordinate;
-use galos_db::{Database, systems::System};
-use crate::Run;
+use galos_db::{Database, bulk::{Batch, SystemRow}, imports::ImportRun};
+use crate::{Run, runs};
+
+// The number of systems written in a single `COPY`.
+const BATCH_SIZE: usize = 10_000;
 
 #[derive(StructOpt, Debug)]
 pub struct Cli {
@@ -11,10 +14,23 @@ pub struct Cli {
     // TODO: Default, when not provided?
     #[structopt(name = "PATH")]
     pub path: String,
+
+    /// Continue the last unfinished import of PATH, or skip it if it's finished
+    #[structopt(long)]
+    pub resume: bool,
 }
 
 impl Run for Cli {
     fn run(&self, db: &Database) {
+        let mut run = match runs::begin(db, "eddb", &self.path, self.resume) {
+            Ok(Some(run)) => run,
+            Ok(None) => {
+                println!("[EDDB] {} has already been imported", self.path);
+                return;
+            },
+            Err(err) => panic!("{}", err),
+        };
+
         let mut dump = match eddb::Dump::csv(&self.path) {
             Ok(d) => d,
             Err(err) => panic!("{}", err),
@@ -24,26 +40,81 @@ impl Run for Cli {
         bar.set_style(ProgressStyle::default_bar()
             .template("[{elapsed_precise}/{eta_precise}] {bar:40} {pos:>7}/{len:7} ({percent}%) {msg}")
             .progress_chars("##-"));
-        for result in bar.wrap_iter(dump.into_iter()) {
-            if let Ok(system) = result {
-                if let Some(address) = system.ed_system_address {
-                    let position = Coordinate {
-                        x: system.coords.x,
-                        y: system.coords.y,
-                        z: system.coords.z
-                    };
-                    task::block_on(async {
-                        let result = System::create(db, address, &system.name, position,
-                            system.population, system.security, system.government,
-                            system.allegiance, system.primary_economy, None, system.updated_at)
-                            .await;
-                        match result {
-                            Ok(_) => bar.set_message(&format!("[EDDB] {}", system.name)),
-                            Err(err) => bar.set_message(&format!("[EDDB ERROR] {}", err)),
-                        }
+
+        // Every row read counts towards `position`, which is how many rows a resumed run skips.
+        // Rows without an address, or which fail to parse or write, count as `failed`.
+        let mut batch = Batch::new();
+        let mut position = run.position;
+        let (mut processed, mut failed) = (run.processed, run.failed);
+        bar.set_position(position as u64);
+        for result in dump.into_iter().skip(run.position as usize) {
+            position += 1;
+            bar.set_position(position as u64);
+            match result {
+                Ok(system) => if let Some(address) = system.ed_system_address {
+                    batch.add_system(SystemRow {
+                        address,
+                        name: system.name,
+                        position: Coordinate {
+                            x: system.coords.x,
+                            y: system.coords.y,
+                            z: system.coords.z
+                        },
+                        population: system.population,
+                        security: system.security,
+                        government: system.government,
+                        allegiance: system.allegiance,
+                        primary_economy: system.primary_economy,
+                        secondary_economy: None,
+                        updated_at: system.updated_at,
                     });
-                }
+                } else {
+                    failed += 1;
+                },
+                Err(err) => {
+                    failed += 1;
+                    bar.set_message(&format!("[EDDB ERROR] {}", err));
+                },
+            }
+
+            if batch.len() >= BATCH_SIZE {
+                flush(db, &bar, &mut batch, &mut run, position, &mut processed, &mut failed);
             }
         }
+        flush(db, &bar, &mut batch, &mut run, position, &mut processed, &mut failed);
+
+        if let Err(err) = task::block_on(run.finish(db)) {
+            bar.set_message(&format!("[EDDB ERROR] {}", err));
+        }
+        bar.finish();
     }
 }
+
+// Writes the batch, then checkpoints the run at `position`.
+fn flush(
+    db: &Database,
+    bar: &ProgressBar,
+    batch: &mut Batch,
+    run: &mut ImportRun,
+    position: i64,
+    processed: &mut i64,
+    failed: &mut i64)
+{
+    let rows = batch.len() as i64;
+    task::block_on(async {
+        match batch.flush(db).await {
+            Ok(stats) => {
+                *processed += rows;
+                bar.set_message(&format!("[EDDB] {} systems", stats.systems));
+            },
+            Err(err) => {
+                *failed += rows;
+                bar.set_message(&format!("[EDDB ERROR] {}", err));
+            },
+        }
+
+        if let Err(err) = run.checkpoint(db, position, *processed, *failed).await {
+            bar.set_message(&format!("[EDDB ERROR] {}", err));
+        }
+    });
+}
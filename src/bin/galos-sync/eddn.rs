@@ -1,9 +1,37 @@
-use async_std::task;
+use std::thread;
+use std::time::Duration;
+use async_std::{channel, task};
 use structopt::StructOpt;
-use elite_journal::entry::Event;
-use eddn::{URL, subscribe, Message};
-use galos_db::{Database, systems::System};
-use crate::Run;
+use serde_json::Value;
+use eddn::{URL, Envelope, Message};
+use elite_journal::entry::Entry;
+use galos_db::{Database, dead_letters::DeadLetter, markets::MarketPrice, outfitting::Outfitting};
+use crate::{Run, ingest};
+
+mod subscriber;
+mod filter;
+mod pipeline;
+mod archive;
+
+use self::subscriber::Subscriber;
+use self::archive::Recorder;
+use self::filter::{Filter, Options};
+
+// The schemas we ingest, other messages are ignored.
+const SCHEMAS: &[&str] = &[
+    "https://eddn.edcd.io/schemas/journal/1",
+    "https://eddn.edcd.io/schemas/commodity/3",
+    "https://eddn.edcd.io/schemas/outfitting/2",
+    "https://eddn.edcd.io/schemas/shipyard/2",
+    FSS_DISCOVERY_SCAN,
+    FSS_SIGNAL_DISCOVERED,
+    APPROACH_SETTLEMENT,
+];
+
+// Journal events which EDDN sends with schemas of their own, instead of journal/1.
+const FSS_DISCOVERY_SCAN: &str = "https://eddn.edcd.io/schemas/fssdiscoveryscan/1";
+const FSS_SIGNAL_DISCOVERED: &str = "https://eddn.edcd.io/schemas/fsssignaldiscovered/1";
+const APPROACH_SETTLEMENT: &str = "https://eddn.edcd.io/schemas/approachsettlement/1";
 
 #[derive(StructOpt, Debug)]
 pub struct Cli {
@@ -11,42 +39,270 @@ pub struct Cli {
     #[structopt(short = "r", long = "remote", default_value = URL, help = "ZMQ remote address")]
     pub url: String,
 
-    // TODO: Filters?
+    #[structopt(long, default_value = "60", help = "seconds without a message before reconnecting")]
+    pub heartbeat: u64,
+
+    #[structopt(long, default_value = "4", help = "number of concurrent DB writers")]
+    pub writers: usize,
+
+    #[structopt(long, default_value = "1000", help = "messages buffered by each stage")]
+    pub queue: usize,
+
+    /// Retry messages which failed before, instead of subscribing
+    #[structopt(long)]
+    pub reprocess: bool,
+
+    /// Ingest messages from an archive, optionally gzipped, instead of subscribing
+    #[structopt(long, value_name = "FILE", conflicts_with = "record")]
+    pub replay: Option<String>,
+
+    /// Pace a replay at FACTOR times the speed the messages were received, 1 being real time
+    #[structopt(long, value_name = "FACTOR", requires = "replay", parse(try_from_str = parse_speed))]
+    pub speed: Option<f64>,
+
+    /// Append every message received to an archive, which can be replayed later
+    #[structopt(long, value_name = "FILE")]
+    pub record: Option<String>,
+
+    /// Only ingest messages of a schema, like "journal" or "commodity"
+    #[structopt(long = "schema", number_of_values = 1)]
+    pub schemas: Vec<String>,
+
+    /// Only ingest messages from a software, given as NAME or NAME@VERSION, where the version
+    /// matches as a prefix
+    #[structopt(long, number_of_values = 1)]
+    pub software: Vec<String>,
+
+    /// Only ingest messages from an uploader
+    #[structopt(long = "uploader", number_of_values = 1)]
+    pub uploaders: Vec<String>,
+
+    /// Only ingest messages about systems within --radius of a system
+    #[structopt(long, value_name = "SYSTEM")]
+    pub near: Option<String>,
+
+    #[structopt(long, default_value = "50", help = "radius of --near in Ly")]
+    pub radius: f64,
+
+    /// Only ingest messages about systems where a faction is present
+    #[structopt(long = "faction", number_of_values = 1)]
+    pub factions: Vec<String>,
 }
 
 impl Run for Cli {
     fn run(&self, db: &Database) {
-        for result in subscribe(&self.url) {
-            if let Ok(envelop) = result {
-                process_message(db, envelop.message);
-            } else if let Err(err) = result {
-                println!("{}", err);
-            }
+        if self.reprocess {
+            return reprocess(db);
+        }
+
+        let filter = match Filter::new(db, &Options {
+            schemas: self.schemas.clone(),
+            software: self.software.clone(),
+            uploaders: self.uploaders.clone(),
+            near: self.near.clone(),
+            radius: self.radius,
+            factions: self.factions.clone(),
+        }) {
+            Ok(filter) => filter,
+            Err(err) => return println!("[EDDN ERROR] {}", err),
+        };
+
+        let mut recorder = match self.record.as_deref().map(Recorder::open).transpose() {
+            Ok(recorder) => recorder,
+            Err(err) => return println!("[EDDN ERROR] {}", err),
+        };
+
+        // ZMQ and reading files block, so they get their own thread.
+        let (source, received) = channel::bounded(self.queue);
+        if let Some(path) = self.replay.clone() {
+            let speed = self.speed;
+            thread::spawn(move || archive::replay(&path, speed, source));
+        } else {
+            let url = self.url.clone();
+            let heartbeat = Duration::from_secs(self.heartbeat);
+            thread::spawn(move || {
+                let mut subscriber = Subscriber::new(&url, heartbeat);
+                loop {
+                    let received = subscriber.recv();
+                    if let (Some(recorder), Ok(raw)) = (&mut recorder, &received) {
+                        if let Err(err) = recorder.record(raw) {
+                            println!("[EDDN ERROR] recording: {}", err);
+                        }
+                    }
+                    if task::block_on(source.send(received)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        task::block_on(pipeline::run(db, &filter, received, self.writers.max(1), self.queue));
+    }
+}
+
+fn parse_speed(s: &str) -> Result<f64, String> {
+    let speed: f64 = s.parse().map_err(|e| format!("{}", e))?;
+    if speed > 0. {
+        Ok(speed)
+    } else {
+        Err("speed must be greater than 0".into())
+    }
+}
+
+fn reprocess(db: &Database) {
+    let (mut last_id, mut fixed, mut failed) = (0, 0, 0);
+    loop {
+        let letters = match task::block_on(DeadLetter::fetch_pending(db, last_id, 100)) {
+            Ok(letters) => letters,
+            Err(err) => return println!("[EDDN ERROR] {}", err),
         };
+        if letters.is_empty() {
+            break;
+        }
+
+        for mut letter in letters {
+            last_id = letter.id;
+            // Dead letters already passed the filters they were received with.
+            let error = match decode(&Filter::default(), &letter.raw) {
+                Ok(Some(decoded)) => {
+                    let errors = task::block_on(process_messages(db, decoded.messages));
+                    if errors.is_empty() { None } else { Some(errors.join("; ")) }
+                },
+                Ok(None) => None,
+                Err((_, error)) => Some(error),
+            };
+            if error.is_none() { fixed += 1 } else { failed += 1 }
+            if let Err(err) = task::block_on(letter.reprocessed(db, error.as_deref())) {
+                return println!("[EDDN ERROR] {}", err);
+            }
+        }
+    }
+
+    println!("[EDDN] {} messages reprocessed, {} still fail", fixed, failed);
+}
+
+async fn dead_letter(db: &Database, schema_ref: Option<&str>, raw: &str, error: &str) {
+    println!("[EDDN ERROR] {}", error);
+    if let Err(err) = DeadLetter::create(db, schema_ref, raw, error).await {
+        println!("[EDDN ERROR] storing dead letter: {}", err);
+    }
+}
+
+/// A parsed message, which passed the filters.
+pub struct Decoded {
+    pub schema_ref: Option<String>,
+    /// The uppercased name of the system the message is about, when it has one.
+    pub system: Option<String>,
+    /// Most messages hold one, but a signals message holds a journal entry for each signal.
+    pub messages: Vec<Message>,
+}
+
+// Parses a message, returning `None` when it's ignored or filtered out, or its schema and the
+// error when it can't be parsed.
+fn decode(filter: &Filter, raw: &str) -> Result<Option<Decoded>, (Option<String>, String)> {
+    let value: Value = serde_json::from_str(raw).map_err(|err| (None, err.to_string()))?;
+    let schema_ref = value["$schemaRef"].as_str().map(|s| s.to_string());
+    if !schema_ref.as_deref().map_or(false, |s| SCHEMAS.contains(&s)) || !filter.accepts(&value) {
+        return Ok(None);
+    }
+
+    let system = filter::system_name(&value["message"]);
+    let messages = match journal_entries(schema_ref.as_deref(), &value["message"]) {
+        Some(entries) => entries.map_err(|err| (schema_ref.clone(), err.to_string()))?
+            .into_iter()
+            .map(Message::Journal)
+            .collect(),
+        None => {
+            let envelope: Envelope = serde_json::from_value(value)
+                .map_err(|err| (schema_ref.clone(), err.to_string()))?;
+            vec![envelope.message]
+        },
+    };
+
+    Ok(Some(Decoded { schema_ref, system, messages }))
+}
+
+// The journal entries in a message of one of the single event schemas, which are journal
+// events already, apart from FSS signals being sent together.
+fn journal_entries(schema_ref: Option<&str>, message: &Value) -> Option<serde_json::Result<Vec<Entry>>> {
+    match schema_ref? {
+        FSS_DISCOVERY_SCAN | APPROACH_SETTLEMENT => {
+            Some(serde_json::from_value(message.clone()).map(|entry| vec![entry]))
+        },
+        FSS_SIGNAL_DISCOVERED => {
+            let signals = message["signals"].as_array().map_or(&[][..], |s| s.as_slice());
+            Some(signals.iter().map(|signal| {
+                let mut entry = signal.as_object().cloned().unwrap_or_default();
+                entry.insert("event".into(), "FSSSignalDiscovered".into());
+                entry.insert("SystemAddress".into(), message["SystemAddress"].clone());
+                serde_json::from_value(Value::Object(entry))
+            }).collect())
+        },
+        _ => None,
+    }
+}
+
+// Returns the errors writing the messages, if any.
+async fn process_messages(db: &Database, messages: Vec<Message>) -> Vec<String> {
+    let mut errors = Vec::new();
+    for message in messages {
+        errors.extend(process_message(db, message).await);
     }
+    errors
 }
 
-fn process_message(db: &Database, message: Message) {
-    task::block_on(async {
+// Returns the errors writing the message, if any.
+async fn process_message(db: &Database, message: Message) -> Vec<String> {
+    let mut errors = Vec::new();
     match message {
         Message::Journal(entry) => {
-            if let Some(system) = match entry.event {
-                Event::Location(e) => {
-                    Some(e.system)
-                },
-                Event::FsdJump(e) => {
-                    Some(e.system)
-                },
-                _ => None,
-            } {
-                let result = System::from_journal(db, &system, entry.timestamp).await;
+            // Each message could be from a different commander, so there's no context to
+            // carry between them.
+            for result in ingest::entry(db, entry, &mut ingest::Context::default()).await {
+                match result {
+                    Ok(name) => println!("[EDDN] {}", name),
+                    Err(err) => errors.push(err.to_string()),
+                }
+            }
+        },
+        Message::Commodity(market) => {
+            if let Some(market_id) = market.market_id {
+                let prices: Vec<MarketPrice> = market.commodities.iter().map(|c| {
+                    MarketPrice {
+                        market_id,
+                        commodity: c.name.clone(),
+                        buy_price: c.buy_price,
+                        sell_price: c.sell_price,
+                        stock: c.stock,
+                        demand: c.demand,
+                        updated_at: market.timestamp,
+                    }
+                }).collect();
+
+                let result = MarketPrice::snapshot(db, market_id, &prices, market.timestamp).await;
                 match result {
-                    Ok(_) => println!("[EDDN] {}", system.name),
-                    Err(err) => println!("[EDDN ERROR] {}", err),
+                    Ok(_) => println!("[EDDN] {} market", market.station_name),
+                    Err(err) => errors.push(err.to_string()),
                 }
             }
         },
+        Message::Outfitting(outfitting) => {
+            let result = Outfitting::snapshot_modules(db,
+                outfitting.market_id, &outfitting.modules, outfitting.timestamp).await;
+            match result {
+                Ok(_) => println!("[EDDN] {} outfitting", outfitting.station_name),
+                Err(err) => errors.push(err.to_string()),
+            }
+        },
+        Message::Shipyard(shipyard) => {
+            let result = Outfitting::snapshot_ships(db,
+                shipyard.market_id, &shipyard.ships, shipyard.timestamp).await;
+            match result {
+                Ok(_) => println!("[EDDN] {} shipyard", shipyard.station_name),
+                Err(err) => errors.push(err.to_string()),
+            }
+        },
         _ => {}
     }
-    })
+    errors
 }
@@ -0,0 +1,74 @@
+//! Recording the EDDN stream to a file, and replaying it later without the relay.
+//!
+//! Archives have one envelope's JSON per line, and may be gzipped when replayed.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write, LineWriter};
+use std::thread;
+use std::time::{Duration, Instant};
+use async_std::{channel::Sender, task};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use crate::dump;
+use super::subscriber::Corrupt;
+
+/// Appends every message received to a file.
+pub struct Recorder {
+    file: LineWriter<File>,
+}
+
+impl Recorder {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Recorder { file: LineWriter::new(file) })
+    }
+
+    pub fn record(&mut self, raw: &str) -> io::Result<()> {
+        // Newlines in JSON are only ever whitespace, since they're escaped in strings.
+        writeln!(self.file, "{}", raw.replace(|c| c == '\n' || c == '\r', ""))
+    }
+}
+
+/// Sends every message in the archive to `source`, then closes it.
+///
+/// With a `speed`, messages are sent as far apart as they were received by the gateway,
+/// divided by `speed`, so 1 is real time. Otherwise they're sent as fast as they're written.
+pub fn replay(path: &str, speed: Option<f64>, source: Sender<Result<String, Corrupt>>) {
+    let (bar, lines) = match dump::lines(path) {
+        Ok(opened) => opened,
+        Err(err) => return println!("[EDDN ERROR] {}", err),
+    };
+
+    let mut start: Option<(Instant, DateTime<Utc>)> = None;
+    for line in lines {
+        let raw = match line {
+            Ok(raw) => raw,
+            Err(err) => {
+                bar.set_message(&format!("[EDDN ERROR] {}", err));
+                break;
+            },
+        };
+
+        if let (Some(speed), Some(sent_at)) = (speed, gateway_timestamp(&raw)) {
+            let (started, first) = *start.get_or_insert((Instant::now(), sent_at));
+            let offset = (sent_at - first).to_std().unwrap_or_default();
+            let due = started + Duration::from_secs_f64(offset.as_secs_f64() / speed);
+            let now = Instant::now();
+            if due > now {
+                thread::sleep(due - now);
+            }
+        }
+
+        if task::block_on(source.send(Ok(raw))).is_err() {
+            break;
+        }
+    }
+
+    bar.finish();
+}
+
+fn gateway_timestamp(raw: &str) -> Option<DateTime<Utc>> {
+    let value: Value = serde_json::from_str(raw).ok()?;
+    let timestamp = value["header"]["gatewayTimestamp"].as_str()?;
+    DateTime::parse_from_rfc3339(timestamp).ok().map(|t| t.with_timezone(&Utc))
+}
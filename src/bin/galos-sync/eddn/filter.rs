@@ -0,0 +1,176 @@
+//! Deciding which EDDN messages to ingest, before they're parsed.
+//!
+//! Filters look at the raw JSON, so messages which are filtered out never reach the DB. Each
+//! kind of filter only applies when it's given, and a message must pass all of them.
+//!
+//! Commodity, outfitting and shipyard messages only name their system, so for the sphere and
+//! faction filters the matching systems are looked up once, when the filter is built.
+
+use std::collections::HashSet;
+use std::io;
+use async_std::task;
+use serde_json::Value;
+use elite_journal::system::Coordinate;
+use galos_db::{Error, Database, systems::System, factions::SystemFaction};
+
+/// Filter options, given to `Filter::new`.
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    pub schemas: Vec<String>,
+    pub software: Vec<String>,
+    pub uploaders: Vec<String>,
+    pub near: Option<String>,
+    pub radius: f64,
+    pub factions: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Filter {
+    schemas: Vec<String>,
+    // Names, with an optional version prefix.
+    software: Vec<(String, Option<String>)>,
+    uploaders: HashSet<String>,
+    sphere: Option<Sphere>,
+    factions: Option<Factions>,
+}
+
+#[derive(Debug)]
+struct Sphere {
+    // Every system known by the name.
+    centers: Vec<Coordinate>,
+    radius: f64,
+    systems: HashSet<String>,
+}
+
+#[derive(Debug)]
+struct Factions {
+    names: HashSet<String>,
+    systems: HashSet<String>,
+}
+
+impl Filter {
+    pub fn new(db: &Database, options: &Options) -> Result<Self, Error> {
+        let sphere = match &options.near {
+            Some(name) => {
+                let centers = task::block_on(System::fetch_by_name(db, name))?;
+                if centers.is_empty() {
+                    let err = io::Error::new(io::ErrorKind::NotFound,
+                        format!("no system named {}", name));
+                    return Err(err.into());
+                }
+                let systems = task::block_on(
+                    System::fetch_in_range_by_name(db, options.radius, name))?;
+                Some(Sphere {
+                    centers: centers.into_iter().map(|s| s.position).collect(),
+                    radius: options.radius,
+                    systems: systems.into_iter().map(|s| s.name.to_uppercase()).collect(),
+                })
+            },
+            None => None,
+        };
+
+        let factions = if options.factions.is_empty() {
+            None
+        } else {
+            let names: HashSet<String> = options.factions.iter()
+                .map(|f| f.to_lowercase())
+                .collect();
+            let addresses = task::block_on(
+                SystemFaction::fetch_addresses_by_faction(db, &options.factions))?;
+            let systems = task::block_on(System::fetch_many(db, &addresses))?;
+            Some(Factions {
+                names,
+                systems: systems.into_iter().map(|s| s.name.to_uppercase()).collect(),
+            })
+        };
+
+        Ok(Filter {
+            schemas: options.schemas.iter().map(|s| s.to_lowercase()).collect(),
+            software: options.software.iter().map(|s| {
+                let mut parts = s.splitn(2, '@');
+                let name = parts.next().unwrap_or_default().to_string();
+                (name, parts.next().map(|v| v.to_string()))
+            }).collect(),
+            uploaders: options.uploaders.iter().cloned().collect(),
+            sphere,
+            factions,
+        })
+    }
+
+    /// Whether the message, a whole EDDN envelope, should be ingested.
+    pub fn accepts(&self, envelope: &Value) -> bool {
+        let header = &envelope["header"];
+        let message = &envelope["message"];
+
+        self.accepts_schema(envelope["$schemaRef"].as_str().unwrap_or_default()) &&
+        self.accepts_software(
+            header["softwareName"].as_str().unwrap_or_default(),
+            header["softwareVersion"].as_str().unwrap_or_default()) &&
+        (self.uploaders.is_empty() ||
+            header["uploaderID"].as_str().map_or(false, |u| self.uploaders.contains(u))) &&
+        self.accepts_location(message) &&
+        self.accepts_factions(message)
+    }
+
+    // Schemas are given by name, like "journal" or "commodity".
+    fn accepts_schema(&self, schema_ref: &str) -> bool {
+        let schema_ref = schema_ref.to_lowercase();
+        self.schemas.is_empty() || self.schemas.iter().any(|schema| {
+            schema_ref.contains(&format!("/schemas/{}/", schema))
+        })
+    }
+
+    fn accepts_software(&self, name: &str, version: &str) -> bool {
+        self.software.is_empty() || self.software.iter().any(|(n, v)| {
+            n.eq_ignore_ascii_case(name) && v.as_ref().map_or(true, |v| version.starts_with(v.as_str()))
+        })
+    }
+
+    fn accepts_location(&self, message: &Value) -> bool {
+        let sphere = match &self.sphere {
+            Some(sphere) => sphere,
+            None => return true,
+        };
+
+        if let Some(position) = star_pos(message) {
+            sphere.centers.iter().any(|center| {
+                let (dx, dy, dz) = (
+                    position.x - center.x,
+                    position.y - center.y,
+                    position.z - center.z);
+                (dx * dx + dy * dy + dz * dz).sqrt() <= sphere.radius
+            })
+        } else {
+            system_name(message).map_or(false, |name| sphere.systems.contains(&name))
+        }
+    }
+
+    fn accepts_factions(&self, message: &Value) -> bool {
+        let factions = match &self.factions {
+            Some(factions) => factions,
+            None => return true,
+        };
+
+        match message["Factions"].as_array() {
+            Some(present) => present.iter().any(|f| {
+                f["Name"].as_str().map_or(false, |n| factions.names.contains(&n.to_lowercase()))
+            }),
+            None => system_name(message).map_or(false, |name| factions.systems.contains(&name)),
+        }
+    }
+}
+
+fn star_pos(message: &Value) -> Option<Coordinate> {
+    match message["StarPos"].as_array()?.as_slice() {
+        [x, y, z] => Some(Coordinate { x: x.as_f64()?, y: y.as_f64()?, z: z.as_f64()? }),
+        _ => None,
+    }
+}
+
+/// The uppercased name of the system a message is about. Journal messages use `StarSystem`,
+/// the others use `systemName`.
+pub fn system_name(message: &Value) -> Option<String> {
+    message["StarSystem"].as_str()
+        .or_else(|| message["systemName"].as_str())
+        .map(|name| name.to_uppercase())
+}
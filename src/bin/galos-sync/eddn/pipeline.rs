@@ -0,0 +1,122 @@
+//! Concurrent ingestion of a stream of EDDN messages.
+//!
+//! Messages are parsed and filtered by a single decoder, then queued by system for one of the
+//! writers. All of a system's messages go to the same writer, so they're written in the order
+//! they were received, while other systems are written concurrently. Every queue is bounded,
+//! so a slow DB slows down reading instead of buffering without limit.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use async_std::{channel::{self, Receiver, Sender}, task};
+use futures::future::{self, join_all};
+use galos_db::Database;
+use super::{Decoded, decode, process_messages, dead_letter};
+use super::subscriber::Corrupt;
+use super::filter::Filter;
+
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+struct Job {
+    raw: String,
+    decoded: Decoded,
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    filtered: AtomicUsize,
+    written: AtomicUsize,
+    failed: AtomicUsize,
+}
+
+/// Ingests messages from `source` until it's closed and every queue is drained.
+pub async fn run(db: &Database,
+    filter: &Filter,
+    source: Receiver<Result<String, Corrupt>>,
+    writers: usize,
+    capacity: usize)
+{
+    let stats = Stats::default();
+    let (queues, receivers): (Vec<_>, Vec<_>) = (0..writers)
+        .map(|_| channel::bounded(capacity))
+        .unzip();
+
+    let work = future::join(
+        decoder(db, filter, source.clone(), queues, &stats),
+        join_all(receivers.iter().cloned().map(|queue| writer(db, queue, &stats))));
+    let report = reporter(&source, &receivers, &stats);
+
+    futures::pin_mut!(work, report);
+    future::select(work, report).await;
+}
+
+async fn decoder(db: &Database,
+    filter: &Filter,
+    source: Receiver<Result<String, Corrupt>>,
+    queues: Vec<Sender<Job>>,
+    stats: &Stats)
+{
+    while let Ok(received) = source.recv().await {
+        let raw = match received {
+            Ok(raw) => raw,
+            Err(corrupt) => {
+                dead_letter(db, None, &corrupt.raw, &corrupt.error).await;
+                stats.failed.fetch_add(1, Ordering::Relaxed);
+                continue;
+            },
+        };
+
+        match decode(filter, &raw) {
+            Ok(Some(decoded)) => {
+                let mut hasher = DefaultHasher::new();
+                decoded.system.hash(&mut hasher);
+                let queue = &queues[hasher.finish() as usize % queues.len()];
+                if queue.send(Job { raw, decoded }).await.is_err() {
+                    return;
+                }
+            },
+            Ok(None) => {
+                stats.filtered.fetch_add(1, Ordering::Relaxed);
+            },
+            Err((schema_ref, error)) => {
+                dead_letter(db, schema_ref.as_deref(), &raw, &error).await;
+                stats.failed.fetch_add(1, Ordering::Relaxed);
+            },
+        }
+    }
+}
+
+async fn writer(db: &Database, queue: Receiver<Job>, stats: &Stats) {
+    while let Ok(job) = queue.recv().await {
+        let schema_ref = job.decoded.schema_ref;
+        let errors = process_messages(db, job.decoded.messages).await;
+        if errors.is_empty() {
+            stats.written.fetch_add(1, Ordering::Relaxed);
+        } else {
+            dead_letter(db, schema_ref.as_deref(), &job.raw, &errors.join("; ")).await;
+            stats.failed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+// Never finishes, it's dropped along with the rest of the pipeline.
+async fn reporter(source: &Receiver<Result<String, Corrupt>>, queues: &[Receiver<Job>], stats: &Stats) {
+    let mut last = (Instant::now(), 0);
+    loop {
+        task::sleep(REPORT_INTERVAL).await;
+
+        let written = stats.written.load(Ordering::Relaxed);
+        let rate = (written - last.1) as f64 / last.0.elapsed().as_secs_f64();
+        last = (Instant::now(), written);
+
+        let depths: Vec<String> = queues.iter().map(|q| q.len().to_string()).collect();
+        println!("[EDDN] {:.1} msg/s, {} to decode, [{}] to write, {} written, {} filtered, {} failed",
+            rate,
+            source.len(),
+            depths.join(" "),
+            written,
+            stats.filtered.load(Ordering::Relaxed),
+            stats.failed.load(Ordering::Relaxed));
+    }
+}
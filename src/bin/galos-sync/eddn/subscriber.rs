@@ -0,0 +1,98 @@
+//! A ZMQ subscription to EDDN which survives the relay going away.
+//!
+//! When nothing is received for the heartbeat timeout, or the socket errors, the subscriber
+//! reconnects, waiting exponentially longer between failed attempts.
+
+use std::io::Read;
+use std::thread;
+use std::time::Duration;
+use flate2::read::ZlibDecoder;
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// A message which couldn't be decompressed, with whatever we could make of it.
+#[derive(Debug)]
+pub struct Corrupt {
+    pub raw: String,
+    pub error: String,
+}
+
+pub struct Subscriber {
+    url: String,
+    heartbeat: Duration,
+    backoff: Duration,
+    context: zmq::Context,
+    socket: Option<zmq::Socket>,
+}
+
+impl Subscriber {
+    pub fn new(url: &str, heartbeat: Duration) -> Self {
+        Subscriber {
+            url: url.to_string(),
+            heartbeat,
+            backoff: INITIAL_BACKOFF,
+            context: zmq::Context::new(),
+            socket: None,
+        }
+    }
+
+    /// Blocks until the next message's JSON is received, reconnecting as needed.
+    pub fn recv(&mut self) -> Result<String, Corrupt> {
+        loop {
+            let socket = match self.socket.take() {
+                Some(socket) => socket,
+                None => match self.connect() {
+                    Ok(socket) => socket,
+                    Err(err) => {
+                        println!("[EDDN ERROR] connecting to {}: {}", self.url, err);
+                        self.back_off();
+                        continue;
+                    },
+                },
+            };
+
+            match socket.recv_bytes(0) {
+                Ok(bytes) => {
+                    self.socket = Some(socket);
+                    self.backoff = INITIAL_BACKOFF;
+                    return inflate(&bytes);
+                },
+                Err(zmq::Error::EAGAIN) => {
+                    println!("[EDDN ERROR] nothing received for {}s, reconnecting",
+                        self.heartbeat.as_secs());
+                    self.back_off();
+                },
+                Err(err) => {
+                    println!("[EDDN ERROR] {}, reconnecting", err);
+                    self.back_off();
+                },
+            }
+        }
+    }
+
+    fn connect(&self) -> Result<zmq::Socket, zmq::Error> {
+        let socket = self.context.socket(zmq::SUB)?;
+        socket.set_subscribe(b"")?;
+        socket.set_rcvtimeo(self.heartbeat.as_millis() as i32)?;
+        socket.connect(&self.url)?;
+        Ok(socket)
+    }
+
+    fn back_off(&mut self) {
+        thread::sleep(self.backoff);
+        self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+// EDDN messages are zlib compressed JSON.
+fn inflate(bytes: &[u8]) -> Result<String, Corrupt> {
+    let mut json = String::new();
+    ZlibDecoder::new(bytes).read_to_string(&mut json).map_err(|err| {
+        Corrupt {
+            raw: String::from_utf8_lossy(bytes).into_owned(),
+            error: err.to_string(),
+        }
+    })?;
+    Ok(json)
+}
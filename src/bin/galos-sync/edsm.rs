@@ -2,9 +2,14 @@ use structopt::StructOpt;
 use galos_db::Database;
 use crate::Run;
 
+mod api;
+mod dump;
+
 #[derive(StructOpt, Debug)]
 pub enum Cli {
+    #[structopt(about = "Import a systems or bodies dump, optionally gzipped")]
     File(FileCli),
+    #[structopt(about = "Fetch systems, their factions and stations from EDSM's API")]
     Api(ApiCli),
 }
 
@@ -16,13 +21,59 @@ pub struct FileCli {
     pub path: String,
 }
 
-// TODO: -s single system, -S systems query?
 #[derive(StructOpt, Debug)]
-pub struct ApiCli {}
+pub struct ApiCli {
+    #[structopt(short = "s", long, required_unless = "systems", conflicts_with = "systems",
+        value_name = "NAME", help = "a single system's name")]
+    pub system: Option<String>,
+    #[structopt(short = "S", long, value_name = "QUERY", help = "every system whose name starts with QUERY")]
+    pub systems: Option<String>,
+
+    #[structopt(short = "u", long, default_value = api::URL, help = "EDSM's base URL")]
+    pub url: String,
+}
 
 impl Run for Cli {
-    fn run(&self, _db: &Database) {
-        unimplemented!();
+    fn run(&self, db: &Database) {
+        match self {
+            Cli::File(cli) => {
+                if let Err(err) = dump::import(db, &cli.path) {
+                    panic!("{}", err);
+                }
+            },
+            Cli::Api(cli) => cli.run(db),
+        }
     }
 }
 
+
+impl Run for ApiCli {
+    fn run(&self, db: &Database) {
+        let client = api::Client::new(api::Reqwest::new(), &self.url);
+
+        let systems = match (&self.system, &self.systems) {
+            (Some(name), _) => client.system(name).map(|s| s.into_iter().collect()),
+            (None, Some(query)) => client.systems(query),
+            (None, None) => Ok(vec![]),
+        };
+
+        let systems = match systems {
+            Ok(systems) => systems,
+            Err(err) => {
+                println!("[EDSM ERROR] {}", err);
+                return;
+            },
+        };
+        if systems.is_empty() {
+            println!("[EDSM ERROR] no systems found");
+        }
+
+        for system in systems {
+            match client.sync(db, &system) {
+                Ok(true) => println!("[EDSM] {}", system.name),
+                Ok(false) => println!("[EDSM ERROR] {} has no address or coordinates", system.name),
+                Err(err) => println!("[EDSM ERROR] {}: {}", system.name, err),
+            }
+        }
+    }
+}
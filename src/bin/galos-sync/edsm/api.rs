@@ -0,0 +1,401 @@
+//! EDSM's system API, https://www.edsm.net/en/api-v1 and https://www.edsm.net/en/api-system-v1.
+//!
+//! Requests go through the `Http` trait, so the transport can be swapped out, and every URL is
+//! relative to a configurable base, so a local mock server can stand in for EDSM.
+
+use std::collections::HashMap;
+use std::{error, fmt};
+use async_std::task;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use elite_journal::{prelude::*, system::Coordinate};
+use galos_db::Database;
+use galos_db::systems::System;
+use galos_db::factions::{Faction, SystemFaction};
+use galos_db::stations::Station;
+use crate::names;
+
+pub const URL: &str = "https://www.edsm.net";
+
+#[derive(Debug)]
+pub enum Error {
+    Http(String),
+    Json(serde_json::Error),
+    Db(galos_db::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Http(e) =>
+                write!(f, "{}", e),
+            Error::Json(e) =>
+                write!(f, "{}", e),
+            Error::Db(e) =>
+                write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Error {
+        Error::Json(err)
+    }
+}
+
+impl From<galos_db::Error> for Error {
+    fn from(err: galos_db::Error) -> Error {
+        Error::Db(err)
+    }
+}
+
+/// A blocking HTTP GET, returning the response body.
+pub trait Http {
+    fn get(&self, url: &str) -> Result<String, Error>;
+}
+
+/// The default transport.
+#[derive(Default)]
+pub struct Reqwest(reqwest::blocking::Client);
+
+impl Reqwest {
+    pub fn new() -> Self {
+        Reqwest(reqwest::blocking::Client::new())
+    }
+}
+
+impl Http for Reqwest {
+    fn get(&self, url: &str) -> Result<String, Error> {
+        self.0.get(url).send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.text())
+            .map_err(|err| Error::Http(err.to_string()))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiSystem {
+    pub id64: Option<u64>,
+    pub name: String,
+    pub coords: Option<Coords>,
+    // EDSM returns `[]` for systems without information.
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub information: Option<Information>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub primary_star: Option<PrimaryStar>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Coords {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Information {
+    pub allegiance: Option<String>,
+    pub government: Option<String>,
+    pub population: Option<u64>,
+    pub security: Option<String>,
+    pub economy: Option<String>,
+    pub second_economy: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PrimaryStar {
+    #[serde(rename = "type")]
+    pub ty: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ApiFactions {
+    #[serde(default)]
+    pub factions: Vec<ApiFaction>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiFaction {
+    pub name: String,
+    pub allegiance: Option<String>,
+    pub government: Option<String>,
+    pub influence: f32,
+    pub state: Option<String>,
+    pub happiness: Option<String>,
+    #[serde(default)]
+    pub active_states: Vec<ApiState>,
+    #[serde(default)]
+    pub pending_states: Vec<ApiState>,
+    #[serde(default)]
+    pub recovering_states: Vec<ApiState>,
+    /// Unix time.
+    pub last_update: i64,
+    /// Influence by unix time, with `showHistory=1`.
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub influence_history: Option<HashMap<String, f32>>,
+    /// State by unix time, with `showHistory=1`.
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub state_history: Option<HashMap<String, String>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ApiState {
+    pub state: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ApiStations {
+    #[serde(default)]
+    pub stations: Vec<ApiStation>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiStation {
+    pub market_id: Option<u64>,
+    #[serde(rename = "type")]
+    pub ty: Option<String>,
+    pub name: String,
+    pub distance_to_arrival: Option<f64>,
+    pub allegiance: Option<String>,
+    pub government: Option<String>,
+    pub economy: Option<String>,
+    pub second_economy: Option<String>,
+    #[serde(default)]
+    pub have_market: bool,
+    #[serde(default)]
+    pub have_shipyard: bool,
+    #[serde(default)]
+    pub have_outfitting: bool,
+    #[serde(default)]
+    pub other_services: Vec<String>,
+    pub controlling_faction: Option<ControllingFaction>,
+    pub update_time: Option<UpdateTime>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ControllingFaction {
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateTime {
+    pub information: Option<String>,
+}
+
+// EDSM uses `[]` and `{}` for missing values, as well as `null`.
+fn empty_as_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+    where D: serde::Deserializer<'de>, T: DeserializeOwned
+{
+    match Value::deserialize(deserializer)? {
+        Value::Null => Ok(None),
+        Value::Array(a) if a.is_empty() => Ok(None),
+        Value::Object(o) if o.is_empty() => Ok(None),
+        value => T::deserialize(value).map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+pub struct Client<H: Http = Reqwest> {
+    http: H,
+    base_url: String,
+}
+
+impl<H: Http> Client<H> {
+    pub fn new(http: H, base_url: &str) -> Self {
+        Client { http, base_url: base_url.trim_end_matches('/').to_string() }
+    }
+
+    // Unknown systems are returned as `[]` or `{}`.
+    fn get<T: DeserializeOwned>(&self, path: &str, params: &[(&str, &str)]) -> Result<Option<T>, Error> {
+        let url = reqwest::Url::parse_with_params(&format!("{}{}", self.base_url, path), params)
+            .map_err(|err| Error::Http(err.to_string()))?;
+        let body = self.http.get(url.as_str())?;
+        match serde_json::from_str::<Value>(&body)? {
+            Value::Array(a) if a.is_empty() => Ok(None),
+            Value::Object(o) if o.is_empty() => Ok(None),
+            value => Ok(Some(serde_json::from_value(value)?)),
+        }
+    }
+
+    pub fn system(&self, name: &str) -> Result<Option<ApiSystem>, Error> {
+        self.get("/api-v1/system", &[
+            ("systemName", name),
+            ("showId", "1"),
+            ("showCoordinates", "1"),
+            ("showInformation", "1"),
+            ("showPrimaryStar", "1"),
+        ])
+    }
+
+    /// Systems whose names start with `query`.
+    pub fn systems(&self, query: &str) -> Result<Vec<ApiSystem>, Error> {
+        self.get("/api-v1/systems", &[
+            ("systemName", query),
+            ("showId", "1"),
+            ("showCoordinates", "1"),
+            ("showInformation", "1"),
+            ("showPrimaryStar", "1"),
+        ]).map(Option::unwrap_or_default)
+    }
+
+    pub fn factions(&self, name: &str) -> Result<Option<ApiFactions>, Error> {
+        self.get("/api-system-v1/factions", &[("systemName", name), ("showHistory", "1")])
+    }
+
+    pub fn stations(&self, name: &str) -> Result<Option<ApiStations>, Error> {
+        self.get("/api-system-v1/stations", &[("systemName", name)])
+    }
+
+    /// Fetches a system, its factions and stations, and merges them into the DB. Returns
+    /// `false` when EDSM doesn't know the system's address or position.
+    pub fn sync(&self, db: &Database, system: &ApiSystem) -> Result<bool, Error> {
+        let (address, coords) = match (system.id64, &system.coords) {
+            (Some(address), Some(coords)) => (address, coords),
+            _ => return Ok(false),
+        };
+        let factions = self.factions(&system.name)?.map_or_else(Vec::new, |f| f.factions);
+        let stations = self.stations(&system.name)?.map_or_else(Vec::new, |s| s.stations);
+
+        task::block_on(async {
+            // EDSM doesn't say when a system's information was updated, so use its newest
+            // faction update. Without one, the system is only inserted if it's new.
+            let updated_at = factions.iter()
+                .filter_map(|f| Utc.timestamp_opt(f.last_update, 0).single())
+                .max()
+                .unwrap_or_else(|| Utc.timestamp(0, 0));
+
+            let info = system.information.as_ref();
+            let position = Coordinate { x: coords.x, y: coords.y, z: coords.z };
+            System::create(db, address, &system.name, position,
+                info.and_then(|i| i.population),
+                info.and_then(|i| i.security.as_deref()).and_then(names::security),
+                info.and_then(|i| i.government.as_deref()).and_then(names::government),
+                info.and_then(|i| i.allegiance.as_deref()).and_then(names::allegiance),
+                info.and_then(|i| i.economy.as_deref()).and_then(names::economy),
+                info.and_then(|i| i.second_economy.as_deref()).and_then(names::economy),
+                updated_at).await?;
+
+            let star_class = system.primary_star.as_ref()
+                .and_then(|s| s.ty.as_deref())
+                .and_then(names::star_class);
+            if let Some(star_class) = star_class {
+                System::set_primary_star_class(db, address, &star_class).await?;
+            }
+
+            for faction in &factions {
+                merge_faction(db, address, faction).await?;
+            }
+
+            for station in &stations {
+                merge_station(db, address, station).await?;
+            }
+
+            Ok::<_, Error>(true)
+        })
+    }
+}
+
+// Writes a faction's influence history in chronological order, followed by its current
+// influence and states.
+async fn merge_faction(db: &Database, address: u64, faction: &ApiFaction) -> Result<(), Error> {
+    // Factions are required to have a government and allegiance.
+    let (government, allegiance, updated_at) = match (
+        faction.government.as_deref().and_then(names::government),
+        faction.allegiance.as_deref().and_then(names::allegiance),
+        Utc.timestamp_opt(faction.last_update, 0).single())
+    {
+        (Some(government), Some(allegiance), Some(updated_at)) => (government, allegiance, updated_at),
+        _ => return Ok(()),
+    };
+    let id = Faction::create(db, &faction.name).await?.id as u32;
+
+    let mut states: Vec<(DateTime<Utc>, String)> = history(&faction.state_history);
+    states.sort();
+    let mut influences: Vec<(DateTime<Utc>, f32)> = history(&faction.influence_history);
+    influences.sort_by_key(|(t, _)| *t);
+
+    for (time, influence) in influences.into_iter().filter(|(t, _)| *t < updated_at) {
+        let state = states.iter()
+            .take_while(|(t, _)| *t <= time)
+            .last()
+            .and_then(|(_, s)| names::state(s));
+        SystemFaction::create(db, address, id,
+            state, influence, None, government, allegiance, &[], time).await?;
+    }
+
+    let states: Vec<_> = faction.pending_states.iter()
+        .filter_map(|s| names::state(&s.state).map(|s| (s, Status::Pending)))
+        .chain(faction.active_states.iter()
+            .filter_map(|s| names::state(&s.state).map(|s| (s, Status::Active))))
+        .chain(faction.recovering_states.iter()
+            .filter_map(|s| names::state(&s.state).map(|s| (s, Status::Recovering))))
+        .collect();
+
+    SystemFaction::create(db, address, id,
+        faction.state.as_deref().and_then(names::state),
+        faction.influence,
+        faction.happiness.as_deref().and_then(names::happiness),
+        government,
+        allegiance,
+        &states,
+        updated_at).await?;
+
+    Ok(())
+}
+
+fn history<T: Clone>(history: &Option<HashMap<String, T>>) -> Vec<(DateTime<Utc>, T)> {
+    history.iter().flatten().filter_map(|(time, value)| {
+        let time = Utc.timestamp_opt(time.parse().ok()?, 0).single()?;
+        Some((time, value.clone()))
+    }).collect()
+}
+
+async fn merge_station(db: &Database, address: u64, station: &ApiStation) -> Result<(), Error> {
+    let updated_at = station.update_time.as_ref()
+        .and_then(|t| t.information.as_deref())
+        .and_then(names::timestamp);
+    let (market_id, updated_at) = match (station.market_id, updated_at) {
+        (Some(market_id), Some(updated_at)) => (market_id, updated_at),
+        _ => return Ok(()),
+    };
+
+    let ty = station.ty.as_deref().and_then(names::station_type);
+    let mut services: Vec<String> = station.other_services.iter()
+        .map(|s| names::service(s))
+        .collect();
+    for (have, service) in &[
+        (station.have_market, "commodities"),
+        (station.have_shipyard, "shipyard"),
+        (station.have_outfitting, "outfitting"),
+    ] {
+        if *have {
+            services.push(service.to_string());
+        }
+    }
+
+    let station = Station {
+        market_id,
+        system_address: address,
+        name: station.name.clone(),
+        landing_pad: ty.as_deref().and_then(names::landing_pad),
+        ty,
+        distance_to_arrival: station.distance_to_arrival,
+        services,
+        faction: station.controlling_faction.as_ref().map(|f| f.name.clone()),
+        government: station.government.as_deref().and_then(names::government),
+        allegiance: station.allegiance.as_deref().and_then(names::allegiance),
+        primary_economy: station.economy.as_deref().and_then(names::economy),
+        secondary_economy: station.second_economy.as_deref().and_then(names::economy),
+        updated_at,
+    };
+
+    Station::create(db, &station).await?;
+    Ok(())
+}
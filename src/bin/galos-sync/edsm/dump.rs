@@ -0,0 +1,256 @@
+//! EDSM's nightly dumps, https://www.edsm.net/en/nightly-dumps.
+//!
+//! Each dump is a JSON array with one object per line, so they're read a line at a time rather
+//! than parsed whole, since they're many gigabytes uncompressed.
+
+use std::io;
+use std::path::Path;
+use async_std::task;
+use chrono::{TimeZone, Utc};
+use serde::Deserialize;
+use indicatif::ProgressBar;
+use elite_journal::{prelude::*, system::Coordinate};
+use galos_db::{Error, Database};
+use galos_db::systems::System;
+use galos_db::bulk::{Batch, SystemRow, SystemFactionRow};
+use galos_db::bodies::{Body, BodyType, Ring};
+use crate::{dump, names};
+
+#[derive(Deserialize, Debug)]
+struct DumpSystem {
+    id64: Option<u64>,
+    name: String,
+    coords: Coords,
+    allegiance: Option<String>,
+    government: Option<String>,
+    security: Option<String>,
+    economy: Option<String>,
+    #[serde(rename = "secondEconomy")]
+    second_economy: Option<String>,
+    population: Option<u64>,
+    #[serde(default)]
+    factions: Vec<DumpFaction>,
+    date: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Coords {
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DumpFaction {
+    name: String,
+    allegiance: Option<String>,
+    government: Option<String>,
+    influence: f32,
+    state: Option<String>,
+    happiness: Option<String>,
+    #[serde(default)]
+    active_states: Vec<DumpState>,
+    #[serde(default)]
+    pending_states: Vec<DumpState>,
+    #[serde(default)]
+    recovering_states: Vec<DumpState>,
+    /// Unix time.
+    last_update: i64,
+}
+
+#[derive(Deserialize, Debug)]
+struct DumpState {
+    state: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DumpBody {
+    body_id: Option<u16>,
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+    sub_type: Option<String>,
+    distance_to_arrival: Option<f64>,
+    is_landable: Option<bool>,
+    /// Already in g.
+    gravity: Option<f64>,
+    terraforming_state: Option<String>,
+    atmosphere_type: Option<String>,
+    #[serde(default)]
+    rings: Vec<DumpRing>,
+    system_id64: Option<u64>,
+    update_time: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DumpRing {
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+    /// Megatons.
+    mass: f64,
+    /// Kilometers.
+    inner_radius: f64,
+    /// Kilometers.
+    outer_radius: f64,
+}
+
+// The number of rows written in a single `COPY`.
+const BATCH_SIZE: usize = 10_000;
+
+/// Imports a systems (with coordinates or populated) or bodies dump, optionally gzipped.
+/// Bodies dumps are recognized by their file name.
+pub fn import(db: &Database, path: &str) -> Result<(), Error> {
+    let (bar, lines) = dump::lines(path)?;
+    let bodies = Path::new(path).file_name()
+        .map_or(false, |name| name.to_string_lossy().contains("bodies"));
+    let mut batch = Batch::new();
+
+    for json in lines {
+        let json = json?;
+        if bodies {
+            let result = serde_json::from_str(&json)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err).into())
+                .and_then(|body| task::block_on(create_body(db, &body)));
+            match result {
+                Ok(Some(name)) => bar.set_message(&format!("[EDSM] {}", name)),
+                Ok(None) => {},
+                Err(err) => bar.set_message(&format!("[EDSM ERROR] {}", err)),
+            }
+        } else {
+            match serde_json::from_str(&json) {
+                Ok(system) => add_system(&mut batch, &system),
+                Err(err) => bar.set_message(&format!("[EDSM ERROR] {}", err)),
+            }
+            if batch.len() >= BATCH_SIZE {
+                flush(db, &bar, &mut batch);
+            }
+        }
+    }
+    flush(db, &bar, &mut batch);
+
+    bar.finish();
+    Ok(())
+}
+
+fn flush(db: &Database, bar: &ProgressBar, batch: &mut Batch) {
+    match task::block_on(batch.flush(db)) {
+        Ok(stats) => bar.set_message(&format!("[EDSM] {} systems, {} factions",
+            stats.systems, stats.system_factions)),
+        Err(err) => bar.set_message(&format!("[EDSM ERROR] {}", err)),
+    }
+}
+
+fn add_system(batch: &mut Batch, system: &DumpSystem) {
+    let (address, updated_at) = match (system.id64, names::timestamp(&system.date)) {
+        (Some(address), Some(updated_at)) => (address, updated_at),
+        _ => return,
+    };
+
+    batch.add_system(SystemRow {
+        address,
+        name: system.name.clone(),
+        position: Coordinate {
+            x: system.coords.x,
+            y: system.coords.y,
+            z: system.coords.z,
+        },
+        population: system.population,
+        security: system.security.as_deref().and_then(names::security),
+        government: system.government.as_deref().and_then(names::government),
+        allegiance: system.allegiance.as_deref().and_then(names::allegiance),
+        primary_economy: system.economy.as_deref().and_then(names::economy),
+        secondary_economy: system.second_economy.as_deref().and_then(names::economy),
+        updated_at,
+    });
+
+    for faction in &system.factions {
+        // Factions are required to have a government and allegiance.
+        let (government, allegiance, updated_at) = match (
+            faction.government.as_deref().and_then(names::government),
+            faction.allegiance.as_deref().and_then(names::allegiance),
+            Utc.timestamp_opt(faction.last_update, 0).single())
+        {
+            (Some(government), Some(allegiance), Some(updated_at)) => (government, allegiance, updated_at),
+            _ => continue,
+        };
+
+        let states = faction.pending_states.iter()
+            .filter_map(|s| names::state(&s.state).map(|s| (s, Status::Pending)))
+            .chain(faction.active_states.iter()
+                .filter_map(|s| names::state(&s.state).map(|s| (s, Status::Active))))
+            .chain(faction.recovering_states.iter()
+                .filter_map(|s| names::state(&s.state).map(|s| (s, Status::Recovering))))
+            .collect();
+
+        batch.add_system_faction(SystemFactionRow {
+            system_address: address,
+            faction: faction.name.clone(),
+            state: faction.state.as_deref().and_then(names::state),
+            influence: faction.influence,
+            happiness: faction.happiness.as_deref().and_then(names::happiness),
+            government,
+            allegiance,
+            states: Some(states),
+            updated_at,
+        });
+    }
+}
+
+// Returns the body's name, or `None` when it was skipped.
+async fn create_body(db: &Database, body: &DumpBody) -> Result<Option<String>, Error> {
+    let (system_address, id, updated_at) = match (
+        body.system_id64,
+        body.body_id,
+        names::timestamp(&body.update_time))
+    {
+        (Some(address), Some(id), Some(updated_at)) => (address, id, updated_at),
+        _ => return Ok(None),
+    };
+
+    let sub_type = body.sub_type.as_deref().unwrap_or_default();
+    let ty = match body.ty.as_str() {
+        "Star" => BodyType::Star,
+        "Planet" => BodyType::Planet,
+        _ => BodyType::Belt,
+    };
+
+    let body = Body {
+        system_address,
+        id,
+        name: body.name.clone(),
+        ty,
+        star_class: if ty == BodyType::Star { names::star_class(sub_type) } else { None },
+        planet_class: if ty == BodyType::Planet { names::planet_class(sub_type) } else { None },
+        distance_to_arrival: body.distance_to_arrival.unwrap_or_default(),
+        landable: body.is_landable,
+        gravity: body.gravity,
+        terraform_state: body.terraforming_state.as_deref().and_then(names::terraform_state),
+        atmosphere: body.atmosphere_type.as_deref().and_then(names::atmosphere),
+        // Stored in the journal's units and names.
+        rings: body.rings.iter().map(|ring| {
+            Ring {
+                name: ring.name.clone(),
+                class: format!("eRingClass_{}", ring.ty.replace(' ', "")),
+                inner_radius: ring.inner_radius * 1000.,
+                outer_radius: ring.outer_radius * 1000.,
+                mass: ring.mass,
+            }
+        }).collect(),
+        updated_at,
+    };
+
+    Body::create(db, &body).await?;
+
+    // The arrival star is the system's primary star.
+    if let (BodyType::Star, Some(star_class)) = (body.ty, &body.star_class) {
+        if body.distance_to_arrival == 0. {
+            System::set_primary_star_class(db, system_address, star_class).await?;
+        }
+    }
+
+    Ok(Some(body.name))
+}
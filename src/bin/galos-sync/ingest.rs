@@ -0,0 +1,101 @@
+//! Ingestion of journal entries, shared by the `journal` and `eddn` providers.
+
+use elite_journal::entry::{Entry, Event};
+use galos_db::{Error, Database};
+use galos_db::bodies::Body;
+use galos_db::settlements::Settlement;
+use galos_db::signals::{BodySignal, SystemSignal};
+use galos_db::stations::Station;
+use galos_db::systems::System;
+
+/// State carried between the entries of a single commander's journal.
+#[derive(Debug, Default)]
+pub struct Context {
+    // Only hyperspace jumps say which star we're jumping to, and the system may not exist
+    // until the `FSDJump` which follows.
+    next_star_class: Option<(u64, String)>,
+}
+
+/// Writes everything we know about from an entry, returning a name for each thing written, or
+/// the error writing it. Entries we don't handle return nothing.
+pub async fn entry(db: &Database, entry: Entry, context: &mut Context) -> Vec<Result<String, Error>> {
+    let mut results = Vec::new();
+    let timestamp = entry.timestamp;
+
+    let mut station = None;
+    let system = match entry.event {
+        Event::Location(e) => {
+            if e.docked {
+                station = e.station.map(|s| (e.system.address, s));
+            }
+            Some(e.system)
+        },
+        Event::FsdJump(e) => {
+            Some(e.system)
+        },
+        // We're aboard a fleet carrier, which moves with us.
+        Event::CarrierJump(e) => {
+            if e.docked {
+                station = e.station.map(|s| (e.system.address, s));
+            }
+            Some(e.system)
+        },
+        Event::Docked(e) => {
+            station = Some((e.system_address, e.station));
+            None
+        },
+        Event::StartJump(e) => {
+            if let (Some(address), Some(star_class)) = (e.system_address, e.star_class) {
+                context.next_star_class = Some((address, star_class));
+            }
+            None
+        },
+        Event::Scan(e) => {
+            let result = Body::from_journal(db, &e, timestamp).await;
+            results.push(result.map(|_| e.body_name));
+            None
+        },
+        Event::FssDiscoveryScan(e) => {
+            let result = System::set_body_count(db, e.system_address, e.body_count, e.non_body_count).await;
+            results.push(result.map(|_| format!("{} bodies", e.body_count)));
+            None
+        },
+        Event::FssSignalDiscovered(e) => {
+            let result = SystemSignal::from_journal(db, &e, timestamp).await;
+            results.push(result.map(|s| s.name));
+            None
+        },
+        Event::SaaSignalsFound(e) => {
+            let result = BodySignal::from_journal(db, &e, timestamp).await;
+            results.push(result.map(|_| format!("{} signals", e.body_name)));
+            None
+        },
+        Event::ApproachSettlement(e) => {
+            let result = Settlement::from_journal(db, &e, timestamp).await;
+            results.push(result.map(|s| s.name));
+            None
+        },
+        _ => None,
+    };
+
+    if let Some(system) = system {
+        let result = System::from_journal(db, &system, timestamp).await;
+        results.push(result.map(|_| system.name.clone()));
+
+        if let Some((address, star_class)) = context.next_star_class.take() {
+            if address == system.address {
+                let result = System::set_primary_star_class(db, address, &star_class).await;
+                if let Err(err) = result {
+                    results.push(Err(err));
+                }
+            }
+        }
+    }
+
+    if let Some((address, station)) = station {
+        let result = Station::from_journal(db, address, &station, timestamp).await;
+        results.push(result.map(|_| station.name));
+    }
+
+    results
+}
@@ -1,54 +1,136 @@
+use std::collections::HashMap;
 use std::fs;
+use std::path::{Path, PathBuf};
 use async_std::task;
 use structopt::StructOpt;
 use indicatif::{ProgressBar, ProgressStyle};
-use elite_journal::entry::{self, Event};
-use galos_db::{Database, systems::System};
-use crate::Run;
+use elite_journal::entry::{self, Entry};
+use galos_db::Database;
+use crate::{Run, ingest, runs};
+
+mod follow;
 
 #[derive(StructOpt, Debug)]
 pub struct Cli {
     #[structopt(name = "PATH")]
     pub path: String,
+
+    /// Continue the last unfinished import of each file, skipping finished files
+    #[structopt(long)]
+    pub resume: bool,
+
+    /// After importing, keep ingesting the newest journal as the game writes it
+    #[structopt(short = "f", long)]
+    pub follow: bool,
 }
 
 impl Run for Cli {
     fn run(&self, db: &Database) {
-        let entries = if let Ok(m) = fs::metadata(&self.path) {
-            if m.is_dir() {
-                entry::parse_journal_dir(&self.path).unwrap()
-            } else {
-                entry::parse_journal_file(&self.path).unwrap()
-            }
-        } else {
-            panic!("bad path: {}", self.path);
+        let paths = match fs::metadata(&self.path) {
+            Ok(m) if m.is_dir() => journal_files(&self.path),
+            Ok(_) => vec![PathBuf::from(&self.path)],
+            Err(_) => panic!("bad path: {}", self.path),
         };
 
-        let bar = ProgressBar::new(entries.len() as u64);
+        // Every file is parsed up front, so the progress bar knows how much is left. How much
+        // of each file was read is kept for `--follow`, which carries on from there.
+        let mut files = Vec::new();
+        let mut imported = HashMap::new();
+        for path in paths {
+            let name = path.file_name().map(|n| n.to_os_string());
+            let path = path.to_string_lossy().into_owned();
+            let run = runs::begin(db, "journal", &path, self.resume);
+            // Measured before parsing, so anything the game writes meanwhile is ingested again
+            // by `--follow` rather than missed.
+            let len = follow::complete_lines(Path::new(&path)).unwrap_or(0);
+            if let Some(name) = name {
+                imported.insert(name, len);
+            }
+            let run = match run {
+                Ok(Some(run)) => run,
+                Ok(None) => continue,
+                Err(err) => panic!("{}", err),
+            };
+            let entries = entry::parse_journal_file(&path).unwrap();
+            files.push((run, entries));
+        }
+
+        let remaining: usize = files.iter()
+            .map(|(run, entries)| entries.len().saturating_sub(run.position as usize))
+            .sum();
+        let bar = ProgressBar::new(remaining as u64);
         bar.set_style(ProgressStyle::default_bar()
             .template("[{elapsed_precise}/{eta_precise}] {bar:40} {pos:>7}/{len:7} ({percent}%) {msg}")
             .progress_chars("##-"));
-        for entry in bar.wrap_iter(entries.into_iter()) {
-            task::block_on(async {
-                let option = match entry.event {
-                    Event::Location(e) => {
-                        Some(e.system)
-                    },
-                    Event::FsdJump(e) => {
-                        Some(e.system)
-                    },
-                    _ => None,
-                };
-
-                if let Some(system) = option {
-                    let result = System::from_journal(db, &system, entry.timestamp).await;
-                    match result {
-                        Ok(_) => bar.set_message(&format!("[{}] {}", entry.timestamp, system.name)),
-                        Err(err) => bar.set_message(&format!("[ERROR {}] {}", entry.timestamp, err)),
+        let mut context = ingest::Context::default();
+        for (mut run, entries) in files {
+            let mut position = run.position;
+            let (mut processed, mut failed) = (run.processed, run.failed);
+            for entry in bar.wrap_iter(entries.into_iter().skip(run.position as usize)) {
+                if process_entry(db, &bar, entry, &mut context) {
+                    processed += 1;
+                } else {
+                    failed += 1;
+                }
+                position += 1;
+
+                if position % runs::CHECKPOINT_INTERVAL == 0 {
+                    if let Err(err) = task::block_on(run.checkpoint(db, position, processed, failed)) {
+                        bar.set_message(&format!("[ERROR] {}", err));
                     }
                 }
+            }
+
+            let result = task::block_on(async {
+                run.checkpoint(db, position, processed, failed).await?;
+                run.finish(db).await
             });
+            if let Err(err) = result {
+                bar.set_message(&format!("[ERROR] {}", err));
+            }
         }
         bar.finish();
+
+        if self.follow {
+            let path = Path::new(&self.path);
+            let dir = if path.is_dir() {
+                path
+            } else {
+                path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."))
+            };
+            follow::follow(db, dir, &imported);
+        }
+    }
+}
+
+// The `Journal.*.log` files in a directory, oldest first.
+fn journal_files<P: AsRef<Path>>(dir: P) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir).unwrap()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy())
+                .map_or(false, |name| name.starts_with("Journal.") && name.ends_with(".log"))
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Ingests a single journal entry, returning false if writing any of it failed.
+pub fn process_entry(db: &Database, bar: &ProgressBar, entry: Entry, context: &mut ingest::Context) -> bool {
+    let timestamp = entry.timestamp;
+    let results = task::block_on(ingest::entry(db, entry, context));
+
+    let mut ok = true;
+    for result in results {
+        match result {
+            Ok(name) => bar.set_message(&format!("[{}] {}", timestamp, name)),
+            Err(err) => {
+                ok = false;
+                bar.set_message(&format!("[ERROR {}] {}", timestamp, err));
+            },
+        }
     }
+    ok
 }
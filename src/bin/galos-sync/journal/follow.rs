@@ -0,0 +1,118 @@
+//! Tailing of the live journal, as the game writes it.
+//!
+//! The journal directory is polled for the newest `Journal.*.log`, which is read from where
+//! the import stopped, or from the start for a journal the game started since, then for new
+//! lines as they're appended. This only needs a local path, so it works through a Proton
+//! prefix on Linux too.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+use indicatif::ProgressBar;
+use elite_journal::entry::Entry;
+use galos_db::Database;
+use crate::ingest;
+use super::{journal_files, process_entry};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Tails the newest journal in `dir` forever, switching to newer journals as the game starts
+/// them. `imported` is how many bytes of each journal, by file name, were already ingested,
+/// see `complete_lines`.
+pub fn follow(db: &Database, dir: &Path, imported: &HashMap<OsString, u64>) {
+    let bar = ProgressBar::new_spinner();
+    bar.enable_steady_tick(100);
+
+    let mut context = ingest::Context::default();
+    let mut tail: Option<Tail> = None;
+    loop {
+        if let Some(newest) = journal_files(dir).pop() {
+            if tail.as_ref().map_or(true, |t| t.path != newest) {
+                // Finish the old journal first, it may have been written to since we last
+                // looked.
+                if let Some(mut old) = tail.take() {
+                    read_new(db, &bar, &mut old, &mut context);
+                }
+                let offset = newest.file_name().and_then(|n| imported.get(n)).copied().unwrap_or(0);
+                match Tail::open(&newest, offset) {
+                    Ok(t) => {
+                        bar.set_message(&format!("following {}", newest.display()));
+                        tail = Some(t);
+                    },
+                    Err(err) => bar.set_message(&format!("[ERROR] {}: {}", newest.display(), err)),
+                }
+            }
+        }
+
+        if let Some(t) = tail.as_mut() {
+            read_new(db, &bar, t, &mut context);
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// The length of the file's complete lines, leaving out a line the game is partway through
+/// writing. Following the file from here never starts mid-line.
+pub fn complete_lines(path: &Path) -> io::Result<u64> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    Ok(bytes.iter().rposition(|&b| b == b'\n').map_or(0, |i| i as u64 + 1))
+}
+
+fn read_new(db: &Database, bar: &ProgressBar, tail: &mut Tail, context: &mut ingest::Context) {
+    let lines = match tail.lines() {
+        Ok(lines) => lines,
+        Err(err) => {
+            bar.set_message(&format!("[ERROR] {}: {}", tail.path.display(), err));
+            return;
+        },
+    };
+
+    for line in lines {
+        match serde_json::from_str::<Entry>(&line) {
+            Ok(entry) => { process_entry(db, bar, entry, context); },
+            Err(err) => bar.set_message(&format!("[ERROR] {}: {}", tail.path.display(), err)),
+        }
+    }
+}
+
+// A journal file, and whatever's been read past its last complete line.
+struct Tail {
+    path: PathBuf,
+    file: File,
+    partial: Vec<u8>,
+}
+
+impl Tail {
+    fn open(path: &Path, offset: u64) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        Ok(Tail {
+            path: path.to_path_buf(),
+            file,
+            partial: Vec::new(),
+        })
+    }
+
+    // The complete lines written since the last call.
+    fn lines(&mut self) -> io::Result<Vec<String>> {
+        self.file.read_to_end(&mut self.partial)?;
+
+        let end = match self.partial.iter().rposition(|&b| b == b'\n') {
+            Some(i) => i + 1,
+            None => return Ok(vec![]),
+        };
+        let rest = self.partial.split_off(end);
+        let complete = std::mem::replace(&mut self.partial, rest);
+
+        Ok(complete.split(|&b| b == b'\n')
+            .map(|line| String::from_utf8_lossy(line).trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect())
+    }
+}
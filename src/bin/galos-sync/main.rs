@@ -6,6 +6,12 @@ mod journal;
 mod eddn;
 mod edsm;
 mod eddb;
+mod spansh;
+mod dump;
+mod ingest;
+mod names;
+mod runs;
+mod doctor;
 
 #[derive(StructOpt, Debug)]
 pub enum Cli {
@@ -17,6 +23,10 @@ pub enum Cli {
     Edsm(edsm::Cli),
     #[structopt(about = "Sync from EDDB's nightly dumps")]
     Eddb(eddb::Cli),
+    #[structopt(about = "Sync from Spansh's galaxy dumps")]
+    Spansh(spansh::Cli),
+    #[structopt(about = "Report problems with the synced data, like position collisions")]
+    Doctor(doctor::Cli),
 }
 
 impl Run for Cli {
@@ -26,6 +36,8 @@ impl Run for Cli {
             Cli::Eddn(cli) => cli.run(db),
             Cli::Edsm(cli) => cli.run(db),
             Cli::Eddb(cli) => cli.run(db),
+            Cli::Spansh(cli) => cli.run(db),
+            Cli::Doctor(cli) => cli.run(db),
         }
     }
 }
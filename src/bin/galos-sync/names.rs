@@ -0,0 +1,288 @@
+//! Parsing of the human readable names used in third party dumps, like "High Tech" or
+//! "Prison colony", into the journal's types.
+//!
+//! Names are compared ignoring case, spaces and punctuation. Unknown names, and names like
+//! "None" which mean there isn't a value, are `None`.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use elite_journal::{prelude::*, faction::State};
+use galos_db::stations::LandingPad;
+
+fn normalize(name: &str) -> String {
+    name.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+pub fn security(name: &str) -> Option<Security> {
+    match normalize(name).as_str() {
+        "low" => Some(Security::Low),
+        "medium" => Some(Security::Medium),
+        "high" => Some(Security::High),
+        // Anarchy and lawless systems are stored as NULL.
+        _ => None,
+    }
+}
+
+pub fn government(name: &str) -> Option<Government> {
+    match normalize(name).as_str() {
+        "anarchy" => Some(Government::Anarchy),
+        "carrier" | "fleetcarrier" | "privateownership" => Some(Government::Carrier),
+        "communism" => Some(Government::Communism),
+        "confederacy" => Some(Government::Confederacy),
+        "cooperative" => Some(Government::Cooperative),
+        "corporate" => Some(Government::Corporate),
+        "democracy" => Some(Government::Democracy),
+        "dictatorship" => Some(Government::Dictatorship),
+        "engineer" => Some(Government::Engineer),
+        "feudal" => Some(Government::Feudal),
+        "patronage" => Some(Government::Patronage),
+        "prison" => Some(Government::Prison),
+        "prisoncolony" => Some(Government::PrisonColony),
+        "theocracy" => Some(Government::Theocracy),
+        _ => None,
+    }
+}
+
+pub fn allegiance(name: &str) -> Option<Allegiance> {
+    match normalize(name).as_str() {
+        "alliance" => Some(Allegiance::Alliance),
+        "empire" => Some(Allegiance::Empire),
+        "federation" => Some(Allegiance::Federation),
+        "guardian" => Some(Allegiance::Guardian),
+        "independent" => Some(Allegiance::Independent),
+        "pilotsfederation" => Some(Allegiance::PilotsFederation),
+        "playerpilots" => Some(Allegiance::PlayerPilots),
+        "thargoid" => Some(Allegiance::Thargoid),
+        _ => None,
+    }
+}
+
+pub fn economy(name: &str) -> Option<Economy> {
+    match normalize(name).as_str() {
+        "agriculture" => Some(Economy::Agriculture),
+        "carrier" | "fleetcarrier" | "privateenterprise" => Some(Economy::Carrier),
+        "colony" => Some(Economy::Colony),
+        "extraction" => Some(Economy::Extraction),
+        "hightech" => Some(Economy::HighTech),
+        "industrial" => Some(Economy::Industrial),
+        "military" => Some(Economy::Military),
+        "prison" => Some(Economy::Prison),
+        "refinery" => Some(Economy::Refinery),
+        "service" => Some(Economy::Service),
+        "terraforming" => Some(Economy::Terraforming),
+        "tourism" => Some(Economy::Tourism),
+        _ => None,
+    }
+}
+
+pub fn state(name: &str) -> Option<State> {
+    match normalize(name).as_str() {
+        "blight" => Some(State::Blight),
+        "boom" => Some(State::Boom),
+        "bust" => Some(State::Bust),
+        "civilliberty" => Some(State::CivilLiberty),
+        "civilunrest" => Some(State::CivilUnrest),
+        "civilwar" => Some(State::CivilWar),
+        "coldwar" => Some(State::ColdWar),
+        "colonisation" => Some(State::Colonisation),
+        "drought" => Some(State::Drought),
+        "election" => Some(State::Election),
+        "expansion" => Some(State::Expansion),
+        "famine" => Some(State::Famine),
+        "historicevent" => Some(State::HistoricEvent),
+        "infrastructurefailure" => Some(State::InfrastructureFailure),
+        "investment" => Some(State::Investment),
+        "lockdown" => Some(State::Lockdown),
+        "naturaldisaster" => Some(State::NaturalDisaster),
+        "outbreak" => Some(State::Outbreak),
+        "pirateattack" => Some(State::PirateAttack),
+        "publicholiday" => Some(State::PublicHoliday),
+        "retreat" => Some(State::Retreat),
+        "revolution" => Some(State::Revolution),
+        "technologicalleap" => Some(State::TechnologicalLeap),
+        "terrorism" | "terroristattack" => Some(State::Terrorism),
+        "tradewar" => Some(State::TradeWar),
+        "war" => Some(State::War),
+        _ => None,
+    }
+}
+
+/// Happiness is given either by name, or by the journal's `$Faction_HappinessBand1;` style
+/// symbols.
+pub fn happiness(name: &str) -> Option<Happiness> {
+    match normalize(name).as_str() {
+        "elated" | "factionhappinessband1" => Some(Happiness::Elated),
+        "happy" | "factionhappinessband2" => Some(Happiness::Happy),
+        "discontented" | "factionhappinessband3" => Some(Happiness::Discontented),
+        "unhappy" | "factionhappinessband4" => Some(Happiness::Unhappy),
+        "despondent" | "factionhappinessband5" => Some(Happiness::Despondent),
+        _ => None,
+    }
+}
+
+/// Translates a star's description, like "K (Yellow-Orange) Star" or "White Dwarf (DA) Star",
+/// into its journal star class, like `K` or `DA`.
+pub fn star_class(name: &str) -> Option<String> {
+    let class = match normalize(name).as_str() {
+        "neutronstar" => "N",
+        "blackhole" => "H",
+        "supermassiveblackhole" => "SupermassiveBlackHole",
+        "ttauristar" => "TTS",
+        "herbigaebestar" => "AeBe",
+        "mredgiantstar" => "M_RedGiant",
+        "mredsupergiantstar" => "M_RedSuperGiant",
+        "kyelloworangegiantstar" => "K_OrangeGiant",
+        "abluewhitesupergiantstar" => "A_BlueWhiteSuperGiant",
+        "bbluewhitesupergiantstar" => "B_BlueWhiteSuperGiant",
+        "fwhitesupergiantstar" => "F_WhiteSuperGiant",
+        "gwhiteyellowsupergiantstar" => "G_WhiteSuperGiant",
+        _ => {
+            // Most names put the class first, or in parentheses for white dwarfs and
+            // Wolf-Rayet stars, e.g. "M (Red dwarf) Star" or "Wolf-Rayet C Star". Some carry
+            // a "-type" suffix, e.g. "MS-type Star".
+            let inner = name.find('(').and_then(|i| {
+                name[i + 1..].find(')').map(|j| &name[i + 1..i + 1 + j])
+            });
+            if name.starts_with("White Dwarf") {
+                return inner.map(|c| c.to_string());
+            } else if let Some(rest) = name.strip_prefix("Wolf-Rayet") {
+                let rest = rest.trim_end_matches("Star").trim();
+                return Some(format!("W{}", rest));
+            } else if name.ends_with("Star") {
+                return name.split_whitespace().next()
+                    .map(|c| c.trim_end_matches("-type").to_string());
+            } else {
+                return None;
+            }
+        },
+    };
+
+    Some(class.to_string())
+}
+
+/// Translates a planet's description into its journal planet class, which mostly differ in
+/// punctuation, and "world" being "body" for rocky planets.
+pub fn planet_class(name: &str) -> Option<String> {
+    let class = match normalize(name).as_str() {
+        "metalrichbody" => "Metal rich body",
+        "highmetalcontentworld" | "highmetalcontentbody" => "High metal content body",
+        "rockybody" => "Rocky body",
+        "rockyiceworld" | "rockyicebody" => "Rocky ice body",
+        "icybody" => "Icy body",
+        "earthlikeworld" | "earthlikebody" => "Earthlike body",
+        "waterworld" => "Water world",
+        "ammoniaworld" => "Ammonia world",
+        "watergiant" => "Water giant",
+        "watergiantwithlife" => "Water giant with life",
+        "gasgiantwithwaterbasedlife" => "Gas giant with water based life",
+        "gasgiantwithammoniabasedlife" => "Gas giant with ammonia based life",
+        "classigasgiant" => "Sudarsky class I gas giant",
+        "classiigasgiant" => "Sudarsky class II gas giant",
+        "classiiigasgiant" => "Sudarsky class III gas giant",
+        "classivgasgiant" => "Sudarsky class IV gas giant",
+        "classvgasgiant" => "Sudarsky class V gas giant",
+        "heliumrichgasgiant" => "Helium rich gas giant",
+        "heliumgasgiant" => "Helium gas giant",
+        "" => return None,
+        _ => return Some(name.to_string()),
+    };
+
+    Some(class.to_string())
+}
+
+pub fn terraform_state(name: &str) -> Option<String> {
+    match normalize(name).as_str() {
+        "candidateforterraforming" | "terraformable" => Some("Terraformable".into()),
+        "terraforming" => Some("Terraforming".into()),
+        "terraformed" => Some("Terraformed".into()),
+        _ => None,
+    }
+}
+
+pub fn atmosphere(name: &str) -> Option<String> {
+    match normalize(name).as_str() {
+        "" | "noatmosphere" | "none" => None,
+        _ => Some(name.to_string()),
+    }
+}
+
+/// Translates a station's type, like "Coriolis Starport" or "Planetary Outpost", into its
+/// journal station type, like `Coriolis` or `CraterOutpost`.
+pub fn station_type(name: &str) -> Option<String> {
+    let ty = match normalize(name).as_str() {
+        "coriolisstarport" | "coriolis" => "Coriolis",
+        "orbisstarport" | "orbis" => "Orbis",
+        "ocellusstarport" | "ocellus" => "Ocellus",
+        "bernalstarport" | "bernal" => "Bernal",
+        "outpost" => "Outpost",
+        "asteroidbase" => "AsteroidBase",
+        "planetaryoutpost" | "crateroutpost" => "CraterOutpost",
+        "planetaryport" | "craterport" => "CraterPort",
+        "megaship" => "MegaShip",
+        "fleetcarrier" => "FleetCarrier",
+        "odysseysettlement" | "onfootsettlement" => "OnFootSettlement",
+        "" => return None,
+        _ => return Some(name.to_string()),
+    };
+
+    Some(ty.to_string())
+}
+
+/// The largest landing pad at a station of the given journal station type, when it's always
+/// the same.
+pub fn landing_pad(station_type: &str) -> Option<LandingPad> {
+    match station_type {
+        "Outpost" => Some(LandingPad::Medium),
+        "Coriolis" | "Orbis" | "Ocellus" | "Bernal" | "AsteroidBase" | "CraterOutpost"
+            | "CraterPort" | "MegaShip" | "FleetCarrier" => Some(LandingPad::Large),
+        _ => None,
+    }
+}
+
+/// Translates a station service, like "Universal Cartographics", into its journal symbol,
+/// like `exploration`.
+pub fn service(name: &str) -> String {
+    let service = normalize(name);
+    let symbol = match service.as_str() {
+        "market" | "commodities" => "commodities",
+        "restock" => "rearm",
+        "universalcartographics" => "exploration",
+        "interstellarfactorscontact" => "facilitator",
+        "searchandrescue" => "searchrescue",
+        "technologybroker" => "techBroker",
+        _ => return service,
+    };
+
+    symbol.to_string()
+}
+
+/// Parses the `2021-04-12 21:03:58` style timestamps used by EDSM and Spansh, which are UTC.
+pub fn timestamp(s: &str) -> Option<DateTime<Utc>> {
+    let s = s.trim_end_matches("+00");
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|t| DateTime::<Utc>::from_utc(t, Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn star_classes() {
+        assert_eq!(star_class("K (Yellow-Orange) Star").as_deref(), Some("K"));
+        assert_eq!(star_class("M (Red dwarf) Star").as_deref(), Some("M"));
+        assert_eq!(star_class("White Dwarf (DA) Star").as_deref(), Some("DA"));
+        assert_eq!(star_class("Wolf-Rayet C Star").as_deref(), Some("WC"));
+        assert_eq!(star_class("Wolf-Rayet Star").as_deref(), Some("W"));
+        assert_eq!(star_class("Neutron Star").as_deref(), Some("N"));
+        assert_eq!(star_class("T Tauri Star").as_deref(), Some("TTS"));
+        assert_eq!(star_class("Black Hole").as_deref(), Some("H"));
+        assert_eq!(star_class("C Star").as_deref(), Some("C"));
+    }
+
+    #[test]
+    fn star_classes_with_type_suffix() {
+        assert_eq!(star_class("MS-type Star").as_deref(), Some("MS"));
+        assert_eq!(star_class("S-type Star").as_deref(), Some("S"));
+    }
+}
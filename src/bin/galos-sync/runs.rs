@@ -0,0 +1,49 @@
+//! Checkpointing of file imports, so `--resume` can pick up where a previous run stopped.
+
+use std::fs::File;
+use std::io::{self, Read};
+use async_std::task;
+use sha2::{Digest, Sha256};
+use galos_db::{Error, Database, imports::ImportRun};
+
+/// How many rows, or journal entries, to read between checkpoints.
+pub const CHECKPOINT_INTERVAL: i64 = 1000;
+
+// How much of the start of a file is hashed to recognize it again, without reading multi-GB
+// dumps in full.
+const HASH_PREFIX: u64 = 1 << 20;
+
+/// Starts importing a file. With `resume`, this continues the last run of the same file, or
+/// returns `None` when the file has already been imported.
+///
+/// A file is the same when its path and the hash of its start match. One which has grown since,
+/// like a journal the game is still writing, is continued from where the last run stopped, even
+/// if it finished. One which has shrunk is imported again.
+pub fn begin(db: &Database, source: &str, path: &str, resume: bool) -> Result<Option<ImportRun>, Error> {
+    let (hash, size) = fingerprint(path)?;
+
+    task::block_on(async {
+        if resume {
+            if let Some(mut run) = ImportRun::fetch_latest(db, source, path, &hash).await? {
+                if run.finished && size == run.file_size {
+                    return Ok(None);
+                } else if size >= run.file_size {
+                    run.resume(db, size).await?;
+                    return Ok(Some(run));
+                }
+            }
+        }
+
+        ImportRun::create(db, source, path, &hash, size).await.map(Some)
+    })
+}
+
+// The hash of the file's start, and its size.
+fn fingerprint(path: &str) -> Result<(String, i64), Error> {
+    let file = File::open(path)?;
+    let size = file.metadata()?.len() as i64;
+
+    let mut hasher = Sha256::new();
+    io::copy(&mut file.take(HASH_PREFIX), &mut hasher)?;
+    Ok((format!("{:x}", hasher.finalize()), size))
+}
@@ -0,0 +1,392 @@
+//! Spansh's galaxy dumps, https://spansh.co.uk/dumps.
+//!
+//! Each line of `galaxy.json.gz` (or `galaxy_populated.json.gz`) is a whole system, with its
+//! factions, bodies, stations and their markets nested inside it. Systems are read a batch at a
+//! time, and each batch is copied in together, with everything nested inside its systems.
+
+use std::collections::HashMap;
+use std::io;
+use async_std::task;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use structopt::StructOpt;
+use elite_journal::system::Coordinate;
+use galos_db::{Error, Database};
+use galos_db::bulk::{Batch, SystemRow, SystemFactionRow, MarketRow, OutfittingRow};
+use galos_db::bodies::{Body, BodyType, Ring};
+use galos_db::stations::{LandingPad, Station};
+use galos_db::markets::MarketPrice;
+use crate::{Run, dump, names};
+
+// The number of systems read, and copied in, at a time.
+const BATCH_SIZE: usize = 256;
+
+#[derive(StructOpt, Debug)]
+pub struct Cli {
+    // TODO: Type as a path.
+    #[structopt(name = "PATH")]
+    pub path: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DumpSystem {
+    id64: u64,
+    name: String,
+    coords: Coords,
+    allegiance: Option<String>,
+    government: Option<String>,
+    primary_economy: Option<String>,
+    secondary_economy: Option<String>,
+    security: Option<String>,
+    population: Option<u64>,
+    #[serde(default)]
+    factions: Vec<DumpFaction>,
+    #[serde(default)]
+    bodies: Vec<DumpBody>,
+    #[serde(default)]
+    stations: Vec<DumpStation>,
+    date: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Coords {
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+#[derive(Deserialize, Debug)]
+struct DumpFaction {
+    name: String,
+    allegiance: Option<String>,
+    government: Option<String>,
+    influence: f32,
+    state: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DumpBody {
+    body_id: Option<u16>,
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+    sub_type: Option<String>,
+    distance_to_arrival: Option<f64>,
+    is_landable: Option<bool>,
+    /// Already in g.
+    gravity: Option<f64>,
+    terraforming_state: Option<String>,
+    atmosphere_type: Option<String>,
+    #[serde(default)]
+    rings: Vec<DumpRing>,
+    #[serde(default)]
+    stations: Vec<DumpStation>,
+    update_time: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DumpRing {
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+    /// Megatons.
+    mass: f64,
+    /// Kilometers.
+    inner_radius: f64,
+    /// Kilometers.
+    outer_radius: f64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DumpStation {
+    /// The station's market ID.
+    id: u64,
+    name: String,
+    #[serde(rename = "type")]
+    ty: Option<String>,
+    distance_to_arrival: Option<f64>,
+    controlling_faction: Option<String>,
+    government: Option<String>,
+    allegiance: Option<String>,
+    /// Economies by their percentage share.
+    #[serde(default)]
+    economies: HashMap<String, f64>,
+    #[serde(default)]
+    services: Vec<String>,
+    landing_pads: Option<LandingPads>,
+    market: Option<DumpMarket>,
+    outfitting: Option<DumpOutfitting>,
+    shipyard: Option<DumpShipyard>,
+    update_time: String,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct LandingPads {
+    #[serde(default)]
+    small: u32,
+    #[serde(default)]
+    medium: u32,
+    #[serde(default)]
+    large: u32,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DumpMarket {
+    #[serde(default)]
+    commodities: Vec<DumpCommodity>,
+    update_time: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DumpCommodity {
+    symbol: String,
+    buy_price: u32,
+    sell_price: u32,
+    supply: u32,
+    demand: u32,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DumpOutfitting {
+    #[serde(default)]
+    modules: Vec<DumpItem>,
+    update_time: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DumpShipyard {
+    #[serde(default)]
+    ships: Vec<DumpItem>,
+    update_time: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct DumpItem {
+    symbol: String,
+}
+
+impl Run for Cli {
+    fn run(&self, db: &Database) {
+        if let Err(err) = import(db, &self.path) {
+            panic!("{}", err);
+        }
+    }
+}
+
+pub fn import(db: &Database, path: &str) -> Result<(), Error> {
+    let (bar, lines) = dump::lines(path)?;
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    let mut lines = lines.peekable();
+
+    while lines.peek().is_some() {
+        batch.clear();
+        for json in lines.by_ref().take(BATCH_SIZE) {
+            match serde_json::from_str::<DumpSystem>(&json?) {
+                Ok(system) => batch.push(system),
+                Err(err) => bar.set_message(&format!("[SPANSH ERROR] {}", err)),
+            }
+        }
+
+        let mut rows = Batch::new();
+        for system in &batch {
+            if let Err(err) = add_system(&mut rows, system) {
+                bar.set_message(&format!("[SPANSH ERROR] {}: {}", system.name, err));
+            }
+        }
+        match task::block_on(rows.flush(db)) {
+            Ok(_) => if let Some(system) = batch.last() {
+                bar.set_message(&format!("[SPANSH] {}", system.name));
+            },
+            Err(err) => bar.set_message(&format!("[SPANSH ERROR] {}", err)),
+        }
+    }
+
+    bar.finish();
+    Ok(())
+}
+
+fn add_system(batch: &mut Batch, system: &DumpSystem) -> Result<(), Error> {
+    let updated_at = timestamp(&system.date)?;
+
+    batch.add_system(SystemRow {
+        address: system.id64,
+        name: system.name.clone(),
+        position: Coordinate {
+            x: system.coords.x,
+            y: system.coords.y,
+            z: system.coords.z,
+        },
+        population: system.population,
+        security: system.security.as_deref().and_then(names::security),
+        government: system.government.as_deref().and_then(names::government),
+        allegiance: system.allegiance.as_deref().and_then(names::allegiance),
+        primary_economy: system.primary_economy.as_deref().and_then(names::economy),
+        secondary_economy: system.secondary_economy.as_deref().and_then(names::economy),
+        updated_at,
+    });
+
+    for faction in &system.factions {
+        // Factions are required to have a government and allegiance.
+        let (government, allegiance) = match (
+            faction.government.as_deref().and_then(names::government),
+            faction.allegiance.as_deref().and_then(names::allegiance))
+        {
+            (Some(government), Some(allegiance)) => (government, allegiance),
+            _ => continue,
+        };
+
+        // Spansh only has each faction's primary state, so the states stored from other sources
+        // are kept.
+        batch.add_system_faction(SystemFactionRow {
+            system_address: system.id64,
+            faction: faction.name.clone(),
+            state: faction.state.as_deref().and_then(names::state),
+            influence: faction.influence,
+            happiness: None,
+            government,
+            allegiance,
+            states: None,
+            updated_at,
+        });
+    }
+
+    for body in &system.bodies {
+        add_body(batch, system.id64, body, updated_at)?;
+        for station in &body.stations {
+            add_station(batch, system.id64, station)?;
+        }
+    }
+
+    for station in &system.stations {
+        add_station(batch, system.id64, station)?;
+    }
+
+    Ok(())
+}
+
+fn add_body(batch: &mut Batch, system_address: u64, body: &DumpBody, system_updated_at: DateTime<Utc>)
+    -> Result<(), Error>
+{
+    let id = match body.body_id {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    let updated_at = match &body.update_time {
+        Some(time) => timestamp(time)?,
+        None => system_updated_at,
+    };
+
+    let sub_type = body.sub_type.as_deref().unwrap_or_default();
+    let ty = match body.ty.as_str() {
+        "Star" => BodyType::Star,
+        "Planet" => BodyType::Planet,
+        _ => BodyType::Belt,
+    };
+
+    // The arrival star is the system's primary star, which the batch records.
+    batch.add_body(Body {
+        system_address,
+        id,
+        name: body.name.clone(),
+        ty,
+        star_class: if ty == BodyType::Star { names::star_class(sub_type) } else { None },
+        planet_class: if ty == BodyType::Planet { names::planet_class(sub_type) } else { None },
+        distance_to_arrival: body.distance_to_arrival.unwrap_or_default(),
+        landable: body.is_landable,
+        gravity: body.gravity,
+        terraform_state: body.terraforming_state.as_deref().and_then(names::terraform_state),
+        atmosphere: body.atmosphere_type.as_deref().and_then(names::atmosphere),
+        // Stored in the journal's units and names.
+        rings: body.rings.iter().map(|ring| {
+            Ring {
+                name: ring.name.clone(),
+                class: format!("eRingClass_{}", ring.ty.replace(' ', "")),
+                inner_radius: ring.inner_radius * 1000.,
+                outer_radius: ring.outer_radius * 1000.,
+                mass: ring.mass,
+            }
+        }).collect(),
+        updated_at,
+    });
+
+    Ok(())
+}
+
+fn add_station(batch: &mut Batch, system_address: u64, station: &DumpStation) -> Result<(), Error> {
+    let ty = station.ty.as_deref().and_then(names::station_type);
+    let pads = station.landing_pads.as_ref();
+    let landing_pad = match pads {
+        Some(pads) if pads.large > 0 => Some(LandingPad::Large),
+        Some(pads) if pads.medium > 0 => Some(LandingPad::Medium),
+        Some(pads) if pads.small > 0 => Some(LandingPad::Small),
+        _ => ty.as_deref().and_then(names::landing_pad),
+    };
+
+    let mut economies: Vec<(&String, &f64)> = station.economies.iter().collect();
+    economies.sort_by(|a, b| b.1.partial_cmp(a.1).unwrap_or(std::cmp::Ordering::Equal));
+    let mut economies = economies.into_iter().filter_map(|(name, _)| names::economy(name));
+
+    batch.add_station(Station {
+        market_id: station.id,
+        system_address,
+        name: station.name.clone(),
+        landing_pad,
+        ty,
+        distance_to_arrival: station.distance_to_arrival,
+        services: station.services.iter().map(|s| names::service(s)).collect(),
+        faction: station.controlling_faction.clone(),
+        government: station.government.as_deref().and_then(names::government),
+        allegiance: station.allegiance.as_deref().and_then(names::allegiance),
+        primary_economy: economies.next(),
+        secondary_economy: economies.next(),
+        updated_at: timestamp(&station.update_time)?,
+    });
+
+    if let Some(market) = &station.market {
+        let updated_at = timestamp(&market.update_time)?;
+        let prices: Vec<MarketPrice> = market.commodities.iter().map(|commodity| {
+            MarketPrice {
+                market_id: station.id,
+                commodity: commodity.symbol.to_lowercase(),
+                buy_price: commodity.buy_price,
+                sell_price: commodity.sell_price,
+                stock: commodity.supply,
+                demand: commodity.demand,
+                updated_at,
+            }
+        }).collect();
+        batch.add_market(MarketRow { market_id: station.id, prices, updated_at });
+    }
+
+    if let Some(outfitting) = &station.outfitting {
+        batch.add_modules(OutfittingRow {
+            market_id: station.id,
+            items: outfitting.modules.iter().map(|m| m.symbol.clone()).collect(),
+            updated_at: timestamp(&outfitting.update_time)?,
+        });
+    }
+
+    if let Some(shipyard) = &station.shipyard {
+        batch.add_ships(OutfittingRow {
+            market_id: station.id,
+            items: shipyard.ships.iter().map(|s| s.symbol.clone()).collect(),
+            updated_at: timestamp(&shipyard.update_time)?,
+        });
+    }
+
+    Ok(())
+}
+
+fn timestamp(s: &str) -> Result<DateTime<Utc>, Error> {
+    names::timestamp(s).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("invalid timestamp `{}`", s)).into()
+    })
+}
@@ -0,0 +1,76 @@
+//! Choosing between systems which share a name.
+
+use std::io::{self, BufRead, Write};
+use std::process;
+use prettytable::{format, Table};
+use galos_db::{Database, systems::System};
+
+/// The system known by `name`. When several systems share the name, they're listed and the
+/// user is asked to choose one by its address or coordinates.
+///
+/// Exits when there's no such system, or when a choice is needed but stdin isn't a terminal.
+pub async fn system(db: &Database, name: &str) -> System {
+    let mut systems = System::fetch_by_name(db, name).await.unwrap();
+    match systems.len() {
+        0 => {
+            eprintln!("no system named {}", name);
+            process::exit(1);
+        },
+        1 => return systems.remove(0),
+        _ => {},
+    }
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(row!["Address", "Name", "Position"]);
+    for system in &systems {
+        let p = &system.position;
+        table.add_row(row![system.address, system.name,
+            format!("{:.5}, {:.5}, {:.5}", p.x, p.y, p.z)]);
+    }
+    println!("{} systems are named {}:", systems.len(), name);
+    table.printstd();
+
+    if !termion::is_tty(&io::stdin()) {
+        eprintln!("{} is ambiguous", name);
+        process::exit(1);
+    }
+
+    let stdin = io::stdin();
+    loop {
+        print!("Which {}? (address or x, y, z) ", name);
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            process::exit(1);
+        }
+        if let Some(i) = choose(&systems, line.trim()) {
+            return systems.swap_remove(i);
+        }
+    }
+}
+
+// Coordinates pick the nearest system, since they're likely rounded.
+fn choose(systems: &[System], input: &str) -> Option<usize> {
+    if let Ok(address) = input.parse::<i64>() {
+        return systems.iter().position(|s| s.address == address);
+    }
+
+    let coordinates = input.split(',')
+        .map(|c| c.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+    match coordinates[..] {
+        [x, y, z] => {
+            let distance = |s: &System| {
+                let p = &s.position;
+                (p.x - x).powi(2) + (p.y - y).powi(2) + (p.z - z).powi(2)
+            };
+            (0..systems.len()).min_by(|&a, &b| {
+                distance(&systems[a]).partial_cmp(&distance(&systems[b])).unwrap()
+            })
+        },
+        _ => None,
+    }
+}
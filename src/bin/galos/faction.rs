@@ -0,0 +1,101 @@
+use std::collections::{BTreeMap, HashMap};
+use async_std::task;
+use chrono::{DateTime, Duration, Utc};
+use itertools::Itertools;
+use structopt::StructOpt;
+use elite_journal::prelude::*;
+use galos_db::{Database, systems::System, factions::{Faction, SystemFaction}};
+use galos::Run;
+use crate::choose;
+
+#[derive(StructOpt, Debug)]
+pub enum Cli {
+    #[structopt(about = "Chart a faction's states over time in each of its systems")]
+    Timeline(TimelineCli),
+}
+
+#[derive(StructOpt, Debug)]
+pub struct TimelineCli {
+    #[structopt(name = "FACTION")]
+    faction: String,
+
+    /// Only chart a single system
+    #[structopt(short = "s", long = "system")]
+    system: Option<String>,
+    #[structopt(default_value = "30", short = "d", long, help = "number of days to chart")]
+    days: i64,
+    #[structopt(default_value = "60", short = "w", long, help = "width of the chart in columns")]
+    width: usize,
+}
+
+impl Run for Cli {
+    fn run(&self, db: &Database) {
+        match self {
+            Cli::Timeline(cli) => cli.run(db),
+        }
+    }
+}
+
+impl Run for TimelineCli {
+    fn run(&self, db: &Database) {
+        let faction = match task::block_on(Faction::fetch_by_name(db, &self.faction)) {
+            Ok(faction) => faction,
+            Err(err) if err.is_not_found() => return eprintln!("faction not found: {}", self.faction),
+            Err(err) => return eprintln!("{}", err),
+        };
+        let (spans, systems) = task::block_on(async {
+            let address = match &self.system {
+                Some(name) => Some(choose::system(db, name).await.address as u64),
+                None => None,
+            };
+            let spans = SystemFaction::state_timeline(db, address, faction.id as u32).await.unwrap();
+            let addresses: Vec<i64> = spans.iter().map(|s| s.system_address as i64).dedup().collect();
+            let systems = System::fetch_many(db, &addresses).await.unwrap();
+            (spans, systems)
+        });
+        let names: HashMap<u64, String> = systems.into_iter()
+            .map(|s| (s.address as u64, s.name))
+            .collect();
+
+        let end = Utc::now();
+        let start = end - Duration::days(self.days);
+        let width = self.width.max(20);
+        let column = |t: DateTime<Utc>| {
+            let elapsed = (t - start).num_seconds() as f64 / (end - start).num_seconds() as f64;
+            ((elapsed * width as f64) as isize).max(0).min(width as isize) as usize
+        };
+
+        println!("{:24} {:<w$}{}", "", start.format("%Y-%m-%d"), end.format("%Y-%m-%d"), w = width - 10);
+        for (address, spans) in &spans.iter().group_by(|s| s.system_address) {
+            // One row for each state, showing its pending, active and recovering periods.
+            let mut rows: BTreeMap<String, Vec<char>> = BTreeMap::new();
+            for span in spans {
+                let ended_at = span.ended_at.unwrap_or(end);
+                if ended_at < start {
+                    continue;
+                }
+
+                let row = rows.entry(format!("{:?}", span.state)).or_insert_with(|| vec!['·'; width]);
+                let from = column(span.started_at);
+                let to = column(ended_at).max(from + 1).min(width);
+                let glyph = match span.status {
+                    Status::Pending => '░',
+                    Status::Active => '█',
+                    Status::Recovering => '▒',
+                };
+                for c in &mut row[from.min(width - 1)..to] {
+                    *c = glyph;
+                }
+            }
+
+            if rows.is_empty() {
+                continue;
+            }
+            println!("{}", names.get(&address).map_or("UNKNOWN", |n| n.as_str()));
+            for (state, row) in rows {
+                println!("  {:22} {}", state, row.into_iter().collect::<String>());
+            }
+        }
+        println!("\n░ pending  █ active  ▒ recovering");
+    }
+}
@@ -1,7 +1,7 @@
 #[macro_use]
 extern crate prettytable;
 
-use structopt::StructOpt;
+use structopt::{StructOpt, clap::AppSettings};
 use galos_db::{Error, Database};
 use galos::Run;
 
@@ -17,8 +17,15 @@ struct Cli {
 enum Subcommand {
     #[structopt(about = "Search for systems, bodies, stations, factions, etc")]
     Search(search::Cli),
-    #[structopt(about = "Plot routes between to and from many systems")]
+    #[structopt(about = "Plot routes between to and from many systems",
+                setting = AppSettings::AllowLeadingHyphen)]
     Route(route::Cli),
+    #[structopt(about = "Find profitable trade routes nearby")]
+    Trade(trade::Cli),
+    #[structopt(about = "Look into a faction's history")]
+    Faction(faction::Cli),
+    #[structopt(about = "Look into a system's history")]
+    System(system::Cli),
 }
 
 impl Run for Subcommand {
@@ -26,6 +33,9 @@ impl Run for Subcommand {
         match self {
             Subcommand::Search(cli) => cli.run(db),
             Subcommand::Route(cli)  => cli.run(db),
+            Subcommand::Trade(cli)  => cli.run(db),
+            Subcommand::Faction(cli) => cli.run(db),
+            Subcommand::System(cli)  => cli.run(db),
         }
     }
 }
@@ -45,3 +55,7 @@ async fn main() -> Result<(), Error> {
 
 mod search;
 mod route;
+mod trade;
+mod choose;
+mod faction;
+mod system;
@@ -1,16 +1,21 @@
+use std::collections::HashMap;
+use std::process;
 use async_std::task;
 use itertools::Itertools;
 use structopt::StructOpt;
 use indicatif::{ProgressBar, ProgressStyle};
 use prettytable::{format, Table};
-use galos_db::{Database, systems::{ModuleClass, System}};
-use galos::Run;
+use galos_db::{Database, spatial::SpatialIndex, systems::{Jump, ModuleClass, Ship, System}};
+use galos::{Run, route::Route, tour};
+use crate::choose;
 
 #[derive(StructOpt, Debug)]
 pub struct Cli {
-    // #[structopt(parse(lalrpop(Route)))]
-    start: String,
-    end: String,
+    /// A route expression, e.g. `"Wolf 397 -> Sol + Meliae -> Nagalinn | Sol"`, quoted since
+    /// `->`, `|` and parentheses mean something to the shell. Or with `--tour` the systems to
+    /// visit, quoting names with spaces
+    #[structopt(name = "ROUTE", required = true)]
+    route: Vec<String>,
 
     #[structopt(default_value = "7.5", short = "r", long)]
     range: f64,
@@ -19,21 +24,72 @@ pub struct Cli {
     #[structopt(default_value = "48", short = "o", long)]
     optimized_mass: f64,
 
-    #[structopt(default_value = "2", short = "s", long)]
+    #[structopt(default_value = "2", short = "s", long, parse(try_from_str = parse_size))]
     size: u8,
     #[structopt(default_value = "E", short = "c", long)]
     class: ModuleClass,
+    #[structopt(default_value = "2", short = "t", long, help = "fuel tank capacity in tons")]
+    tank: f64,
+
+    /// Minimize fuel used instead of jumps, with the jump range given by the FSD. Refuels at
+    /// scoopable stars, and each leg between stops starts with a full tank.
+    #[structopt(short = "f", long)]
+    fuel: bool,
+
+    /// Supercharge the FSD at neutron stars (x4 range) and white dwarfs (x1.5 range).
+    #[structopt(short = "n", long, conflicts_with = "fuel")]
+    neutron: bool,
+
+    /// Plot jumps with an in-memory index of every system, cached at this path. The cache is
+    /// rebuilt when systems have been synced since. Fuel routes don't use the index.
+    #[structopt(short = "i", long, conflicts_with = "fuel")]
+    index: Option<String>,
+    #[structopt(default_value = "10", long, help = "index grid cell size in Ly")]
+    cell_size: f64,
+
+    /// Find a near optimal order to visit every system, starting at the first and ending at
+    /// the last.
+    #[structopt(long, conflicts_with_all = &["fuel", "neutron"])]
+    tour: bool,
+    /// Return to the first system, instead of ending the tour at the last.
+    #[structopt(short = "l", long = "loop", requires = "tour")]
+    closed: bool,
+    /// Order the tour by plotted jumps instead of straight-line distance, which plots a route
+    /// between every pair of systems.
+    #[structopt(short = "j", long, requires = "tour")]
+    jumps: bool,
 }
 
 impl Run for Cli {
     fn run(&self, db: &Database) {
+        if self.tour {
+            return self.run_tour(db);
+        }
+
+        let route: Route = match self.route.join(" ").parse() {
+            Ok(r) => r,
+            Err(err) => {
+                eprintln!("invalid route: {}", err);
+                process::exit(1);
+            },
+        };
+
         let spinner = ProgressBar::new_spinner();
         spinner.enable_steady_tick(100);
         spinner.set_message("Finding systems...");
-        let (start, end) = task::block_on(async {
-            let start = System::fetch_by_name(db, &self.start).await.unwrap();
-            let end   = System::fetch_by_name(db, &self.end).await.unwrap();
-            (start, end)
+        let systems = task::block_on(async {
+            let mut systems = HashMap::new();
+            for name in route.orderings().into_iter().flatten() {
+                if !systems.contains_key(&name.to_uppercase()) {
+                    let system = choose::system(db, name).await;
+                    systems.insert(name.to_uppercase(), system);
+                }
+            }
+            systems
+        });
+        let index = self.index.as_ref().map(|path| {
+            spinner.set_message("Loading index...");
+            task::block_on(SpatialIndex::load_cached(db, path, self.cell_size)).unwrap()
         });
         spinner.finish_with_message("Input systems found, finding route...");
 
@@ -64,33 +120,184 @@ impl Run for Cli {
         );
         spinner.enable_steady_tick(250);
 
+        let ship = Ship {
+            mass: self.total_mass,
+            optimal_mass: self.optimized_mass,
+            size: self.size,
+            class: self.class,
+            tank: self.tank,
+        };
+
+        // Every leg is plotted at most once, and reused for the final table.
+        let mut legs: HashMap<(i64, i64), Vec<Jump>> = HashMap::new();
+        let cheapest = route.cheapest(|a, b| {
+            let start = &systems[&a.to_uppercase()];
+            let end = &systems[&b.to_uppercase()];
+            spinner.set_message(&format!("{} -> {}", start.name, end.name));
+            let (path, cost) = if self.fuel {
+                start.route_with_fuel(db, end, &ship).unwrap()?
+            } else {
+                let (path, cost) = match (&index, self.neutron) {
+                    (Some(index), false) =>
+                        start.route_to_indexed(db, index, end, self.range).unwrap()?,
+                    (Some(index), true) =>
+                        start.route_boosted_indexed(db, index, end, self.range).unwrap()?,
+                    (None, false) => start.route_to(db, end, self.range).unwrap()?,
+                    (None, true) => start.route_boosted(db, end, self.range).unwrap()?,
+                };
+                let path = path.into_iter().map(|system| {
+                    Jump { system, fuel: 0., refuel: false }
+                }).collect();
+                (path, cost)
+            };
+            legs.insert((start.address, end.address), path);
+            Some(cost.into_inner())
+        });
+        spinner.finish_and_clear();
+
+        let (stops, cost) = match cheapest {
+            Some(c) => c,
+            None => {
+                println!("no route found");
+                return;
+            }
+        };
+
         let mut table = Table::new();
         table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
-        table.set_titles(row!["Origin", "Destination", "Distance"]);
-        let (route, cost) = start.route_to(db, &end, self.range).unwrap().unwrap();
-        spinner.finish_and_clear();
+        if self.fuel {
+            table.set_titles(row!["Origin", "Destination", "Distance", "Fuel", "Scoop"]);
+        } else if self.neutron {
+            table.set_titles(row!["Origin", "Destination", "Distance", "Boost"]);
+        } else {
+            table.set_titles(row!["Origin", "Destination", "Distance"]);
+        }
         let mut gross = 0.;
-        for (a, b) in route[..].into_iter().tuple_windows() {
-            let d = a.distance(&b);
-            table.add_row(row![a.name, b.name, format!("{:.2} Ly", d)]);
-            gross += d;
+        let mut jumps = 0;
+        for (a, b) in stops.iter().tuple_windows() {
+            let (start, end) = (&systems[&a.to_uppercase()], &systems[&b.to_uppercase()]);
+            for (a, b) in legs[&(start.address, end.address)].iter().tuple_windows() {
+                let d = a.system.distance(&b.system);
+                if self.fuel {
+                    table.add_row(row![a.system.name, b.system.name, format!("{:.2} Ly", d),
+                        format!("{:.2} t", b.fuel), if b.refuel { "yes" } else { "" }]);
+                } else if self.neutron {
+                    let boost = a.system.jump_boost();
+                    table.add_row(row![a.system.name, b.system.name, format!("{:.2} Ly", d),
+                        if boost > 1. { format!("x{}", boost) } else { String::new() }]);
+                } else {
+                    table.add_row(row![a.system.name, b.system.name, format!("{:.2} Ly", d)]);
+                }
+                gross += d;
+                jumps += 1;
+            }
+        }
+        table.printstd();
+
+        let first = &systems[&stops[0].to_uppercase()];
+        let last = &systems[&stops.last().expect("valid route").to_uppercase()];
+        println!("stops: {}", stops.iter().map(|s| &systems[&s.to_uppercase()].name).join(" -> "));
+        if self.fuel {
+            println!("jumps: {}, fuel: {:.2} t, range: {:.2} Ly, path: {:.2} Ly, distance: {:.2} Ly",
+                jumps,
+                cost,
+                ship.jump_range(),
+                gross,
+                first.distance(last));
+        } else {
+            println!("jumps: {:.2}, path: {:.2} Ly, distance: {:.2} Ly",
+                cost,
+                gross,
+                first.distance(last));
+        }
+    }
+}
+
+impl Cli {
+    fn run_tour(&self, db: &Database) {
+        let spinner = ProgressBar::new_spinner();
+        spinner.enable_steady_tick(100);
+        spinner.set_message("Finding systems...");
+        let systems = task::block_on(async {
+            let mut systems = Vec::with_capacity(self.route.len());
+            for name in &self.route {
+                systems.push(choose::system(db, name).await);
+            }
+            systems
+        });
+        let index = self.index.as_ref().map(|path| {
+            spinner.set_message("Loading index...");
+            task::block_on(SpatialIndex::load_cached(db, path, self.cell_size)).unwrap()
+        });
+
+        let plot = |start: &System, end: &System| {
+            spinner.set_message(&format!("{} -> {}", start.name, end.name));
+            if let Some(index) = &index {
+                start.route_to_indexed(db, index, end, self.range).unwrap()
+            } else {
+                start.route_to(db, end, self.range).unwrap()
+            }
+        };
+
+        // With `--jumps` every leg is plotted up front, and reused for the final table.
+        let mut legs = HashMap::new();
+        let n = systems.len();
+        let order = if self.jumps {
+            for (a, start) in systems.iter().enumerate() {
+                for (b, end) in systems.iter().enumerate() {
+                    if start != end {
+                        legs.insert((a, b), plot(start, end));
+                    }
+                }
+            }
+            // Legs which weren't plotted are between the same system.
+            tour::optimise(n, self.closed, |a, b| legs.get(&(a, b)).map_or(0., |leg| {
+                leg.as_ref().map_or(f64::INFINITY, |(_, cost)| cost.into_inner())
+            }))
+        } else {
+            tour::optimise(n, self.closed, |a, b| systems[a].distance(&systems[b]))
+        };
+
+        let mut stops = order;
+        if self.closed {
+            stops.push(stops[0]);
+        }
+
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+        table.set_titles(row!["#", "System", "Distance", "Jumps", "Path"]);
+        table.add_row(row![0, systems[stops[0]].name, "", "", ""]);
+        let (mut jumps, mut distance, mut gross) = (0., 0., 0.);
+        for (i, (&a, &b)) in stops.iter().tuple_windows().enumerate() {
+            let leg = legs.remove(&(a, b)).unwrap_or_else(|| plot(&systems[a], &systems[b]));
+            let (a, b) = (&systems[a], &systems[b]);
+            let d = a.distance(b);
+            distance += d;
+            match leg {
+                Some((route, cost)) => {
+                    let path: f64 = route.iter().tuple_windows().map(|(a, b)| a.distance(b)).sum();
+                    jumps += cost.into_inner();
+                    gross += path;
+                    table.add_row(row![i + 1, b.name, format!("{:.2} Ly", d),
+                        cost, format!("{:.2} Ly", path)]);
+                },
+                None => {
+                    table.add_row(row![i + 1, b.name, format!("{:.2} Ly", d), "-", "no route"]);
+                },
+            }
         }
+        spinner.finish_and_clear();
+
         table.printstd();
-        println!("jumps: {:.2}, path: {:.2} Ly, distance: {:.2} Ly",
-            cost,
-            gross,
-            route[0].distance(&route.last().expect("valid route")));
+        println!("jumps: {:.2}, path: {:.2} Ly, distance: {:.2} Ly", jumps, gross, distance);
     }
 }
 
-// enum Route {
-//     End,
-//     Stop(String),
-//     // `A -> B` specifies a direct path from A to B
-//     Path(Box<Route>, Box<Route>),
-//     // `A + B` specifies a path to both A and B, where the route could either visit
-//     // A or B first
-//     Both(Box<Route>, Box<Route>),
-//     // `A | B` specifies a path to either A or B
-//     Either(Box<Route>, Box<Route>),
-// }
+fn parse_size(s: &str) -> Result<u8, String> {
+    let size = s.parse().map_err(|e| format!("{}", e))?;
+    if Ship::FSD_SIZES.contains(&size) {
+        Ok(size)
+    } else {
+        Err(format!("FSD size must be {} to {}", Ship::FSD_SIZES.start(), Ship::FSD_SIZES.end()))
+    }
+}
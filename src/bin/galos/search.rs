@@ -2,7 +2,9 @@ use async_std::task;
 use structopt::StructOpt;
 use indicatif::{ProgressBar, ProgressStyle};
 use galos_db::{Database, systems::System, factions::{Faction, SystemFaction}};
+use galos_db::outfitting::{self, Availability, Outfitting};
 use galos::Run;
+use crate::choose;
 
 #[derive(StructOpt, Debug)]
 pub struct Cli {
@@ -33,6 +35,21 @@ pub struct Cli {
     #[structopt(short = "c", long = "count")]
     count: bool,
 
+    /// Stations selling a module, e.g. "5A Frame Shift Drive", nearest first
+    #[structopt(short = "m", long = "module", requires = "near")]
+    module: Option<String>,
+
+    /// Stations selling a ship, e.g. "Anaconda", nearest first
+    #[structopt(long = "ship", requires = "near", conflicts_with = "module")]
+    ship: Option<String>,
+
+    /// The system to search for stations near
+    #[structopt(short = "n", long = "near")]
+    near: Option<String>,
+
+    #[structopt(default_value = "20", short = "l", long = "limit")]
+    limit: i64,
+
     // #[structopt(short = "f", long = "filter", parse(from_filter_string))]
     // filters: Vec<String>,
 
@@ -60,6 +77,32 @@ impl Run for Cli {
 
         task::block_on(async {
 
+            if let (Some(near), true) = (&self.near, self.module.is_some() || self.ship.is_some()) {
+                let system = choose::system(db, near).await;
+                let results = if let Some(module) = &self.module {
+                    let symbol = outfitting::module_symbol(module);
+                    Outfitting::fetch_modules_near(db, system.address as u64, self.radius, &symbol, self.limit)
+                        .await.unwrap()
+                } else if let Some(ship) = &self.ship {
+                    let symbol = outfitting::ship_symbol(ship);
+                    Outfitting::fetch_ships_near(db, system.address as u64, self.radius, &symbol, self.limit)
+                        .await.unwrap()
+                } else {
+                    unreachable!()
+                };
+
+                spinner.finish_and_clear();
+
+                if self.count {
+                    println!("{} stations found.", results.len());
+                } else {
+                    for availability in results {
+                        print_availability(&availability);
+                    }
+                }
+                return;
+            }
+
             match (self.system_like.as_ref(), self.faction_like.as_ref()) {
 
 
@@ -142,3 +185,12 @@ fn print_system(system: &System) {
         println!("");
     }
 }
+
+fn print_availability(availability: &Availability) {
+    println!("{} ({}): {:.2} Ly\t\t[{}]",
+        availability.station_name,
+        availability.system_name,
+        availability.distance,
+        availability.updated_at);
+    println!("\t{}", availability.item);
+}
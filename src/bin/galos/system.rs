@@ -0,0 +1,117 @@
+use std::fmt::Debug;
+use async_std::task;
+use structopt::StructOpt;
+use prettytable::{format, Table};
+use galos_db::{Database, systems::{System, SystemHistory}};
+use galos::Run;
+use crate::choose;
+
+#[derive(StructOpt, Debug)]
+pub enum Cli {
+    #[structopt(about = "List the states a system has been seen in")]
+    History(HistoryCli),
+}
+
+#[derive(StructOpt, Debug)]
+pub struct HistoryCli {
+    #[structopt(name = "SYSTEM")]
+    system: String,
+
+    /// Only list changes to one of population, security, government, allegiance or economy
+    #[structopt(short = "f", long, parse(try_from_str = parse_field))]
+    field: Option<Field>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Field {
+    Population,
+    Security,
+    Government,
+    Allegiance,
+    Economy,
+}
+
+impl Run for Cli {
+    fn run(&self, db: &Database) {
+        match self {
+            Cli::History(cli) => cli.run(db),
+        }
+    }
+}
+
+impl Run for HistoryCli {
+    fn run(&self, db: &Database) {
+        let history = task::block_on(async {
+            let system = choose::system(db, &self.system).await;
+            System::history(db, system.address as u64).await
+        });
+        let history = match history {
+            Ok(history) => history,
+            Err(err) => return eprintln!("{}", err),
+        };
+
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+        match self.field {
+            Some(field) => {
+                table.set_titles(row!["Date", format!("{:?}", field)]);
+                // Other fields change too, so consecutive entries can share this one's value.
+                let mut last = None;
+                for entry in &history {
+                    let value = field.value(entry);
+                    if last.as_ref() != Some(&value) {
+                        table.add_row(row![date(entry), value]);
+                    }
+                    last = Some(value);
+                }
+            },
+            None => {
+                table.set_titles(row!["Date", "Population", "Security", "Government",
+                    "Allegiance", "Economy"]);
+                for entry in &history {
+                    table.add_row(row![date(entry),
+                        Field::Population.value(entry),
+                        Field::Security.value(entry),
+                        Field::Government.value(entry),
+                        Field::Allegiance.value(entry),
+                        Field::Economy.value(entry)]);
+                }
+            },
+        }
+        table.printstd();
+    }
+}
+
+impl Field {
+    fn value(&self, entry: &SystemHistory) -> String {
+        match self {
+            Field::Population => entry.population.map_or_else(String::new, |p| p.to_string()),
+            Field::Security => or_blank(entry.security.as_ref()),
+            Field::Government => or_blank(entry.government.as_ref()),
+            Field::Allegiance => or_blank(entry.allegiance.as_ref()),
+            Field::Economy => match (&entry.primary_economy, &entry.secondary_economy) {
+                (Some(primary), Some(secondary)) => format!("{:?} / {:?}", primary, secondary),
+                (primary, _) => or_blank(primary.as_ref()),
+            },
+        }
+    }
+}
+
+fn date(entry: &SystemHistory) -> String {
+    entry.updated_at.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn or_blank<T: Debug>(value: Option<T>) -> String {
+    value.map_or_else(String::new, |v| format!("{:?}", v))
+}
+
+fn parse_field(s: &str) -> Result<Field, String> {
+    match s.to_lowercase().as_str() {
+        "population" => Ok(Field::Population),
+        "security" => Ok(Field::Security),
+        "government" => Ok(Field::Government),
+        "allegiance" => Ok(Field::Allegiance),
+        "economy" => Ok(Field::Economy),
+        _ => Err(format!("unknown field {}", s)),
+    }
+}
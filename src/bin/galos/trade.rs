@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use async_std::task;
+use chrono::{Duration, Utc};
+use itertools::Itertools;
+use structopt::StructOpt;
+use indicatif::ProgressBar;
+use prettytable::{format, Table};
+use galos_db::{Database, markets::{MarketPrice, StationPrice}, stations::LandingPad, systems::System};
+use galos::Run;
+use crate::choose;
+
+#[derive(StructOpt, Debug)]
+pub struct Cli {
+    /// The system to trade around
+    #[structopt(name = "SYSTEM")]
+    system: String,
+
+    #[structopt(default_value = "20", short = "r", long, help = "search radius in Ly")]
+    radius: f64,
+    #[structopt(default_value = "7.5", short = "j", long, help = "jump range in Ly")]
+    jump_range: f64,
+    #[structopt(default_value = "100", short = "c", long, help = "cargo capacity in tons")]
+    cargo: u32,
+    #[structopt(default_value = "S", short = "p", long, help = "required landing pad (S, M or L)")]
+    pad: LandingPad,
+    #[structopt(default_value = "24", short = "a", long, help = "maximum price age in hours")]
+    max_age: i64,
+
+    /// Find A -> B -> A loops instead of single hops
+    #[structopt(short = "l", long = "loop")]
+    round_trip: bool,
+    #[structopt(default_value = "10", short = "n", long)]
+    limit: usize,
+}
+
+// The most profitable cargo to carry from one market to another.
+struct Hop<'a> {
+    from: &'a StationPrice,
+    to: &'a StationPrice,
+    units: u32,
+    profit: u64,
+}
+
+impl Run for Cli {
+    fn run(&self, db: &Database) {
+        let center = task::block_on(choose::system(db, &self.system));
+
+        let spinner = ProgressBar::new_spinner();
+        spinner.enable_steady_tick(100);
+        spinner.set_message("Finding markets...");
+
+        let systems = center.neighbors(db, self.radius);
+        let prices = task::block_on(async {
+            let addresses: Vec<i64> = systems.iter().map(|s| s.address).collect();
+            let since = Utc::now() - Duration::hours(self.max_age);
+            MarketPrice::fetch_in_systems(db, &addresses, Some(self.pad), since).await.unwrap()
+        });
+
+        let systems: HashMap<u64, System> = systems.into_iter().map(|s| (s.address as u64, s)).collect();
+        let markets = prices.iter().into_group_map_by(|p| p.price.market_id);
+
+        // Plotting is much slower than pricing, so only the listed trades are plotted, once per
+        // pair of systems.
+        let mut legs = HashMap::new();
+        let mut jumps = |a: &StationPrice, b: &StationPrice| -> Option<f64> {
+            let (a, b) = (a.system_address, b.system_address);
+            *legs.entry((a, b)).or_insert_with(|| {
+                let (start, end) = (&systems[&a], &systems[&b]);
+                spinner.set_message(&format!("{} -> {}", start.name, end.name));
+                start.route_to(db, end, self.jump_range).unwrap().map(|(_, cost)| cost.into_inner())
+            })
+        };
+
+        let mut hops = HashMap::new();
+        for ((&a, from), (&b, to)) in markets.iter().cartesian_product(markets.iter()) {
+            if a != b {
+                if let Some(hop) = self.best_hop(from, to) {
+                    hops.insert((a, b), hop);
+                }
+            }
+        }
+
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+        table.set_titles(row!["From", "Buy", "To", "Sell", "Units", "Profit", "Jumps"]);
+
+        if self.round_trip {
+            let mut loops: Vec<(&Hop, &Hop)> = hops.iter()
+                .filter(|((a, b), _)| a < b)
+                .filter_map(|((a, b), there)| Some((there, hops.get(&(*b, *a))?)))
+                .collect();
+            loops.sort_by_key(|(there, back)| std::cmp::Reverse(there.profit + back.profit));
+
+            for (there, back) in loops.into_iter().take(self.limit) {
+                let there_jumps = jumps(there.from, there.to);
+                let back_jumps = jumps(back.from, back.to);
+                table.add_row(self.hop_row(&systems, there, there_jumps));
+                table.add_row(self.hop_row(&systems, back, back_jumps));
+                let total = there_jumps.and_then(|a| back_jumps.map(|b| a + b));
+                table.add_row(row!["", "", "", "", "", there.profit + back.profit, format_jumps(total)]);
+            }
+        } else {
+            let mut hops: Vec<&Hop> = hops.values().collect();
+            hops.sort_by_key(|hop| std::cmp::Reverse(hop.profit));
+
+            for hop in hops.into_iter().take(self.limit) {
+                table.add_row(self.hop_row(&systems, hop, jumps(hop.from, hop.to)));
+            }
+        }
+        spinner.finish_and_clear();
+
+        table.printstd();
+    }
+}
+
+impl Cli {
+    fn best_hop<'a>(&self, from: &[&'a StationPrice], to: &[&'a StationPrice]) -> Option<Hop<'a>> {
+        let sells: HashMap<&str, &StationPrice> = to.iter()
+            .filter(|p| p.price.sell_price > 0)
+            .map(|p| (p.price.commodity.as_str(), *p))
+            .collect();
+
+        from.iter()
+            .filter(|p| p.price.buy_price > 0 && p.price.stock > 0)
+            .filter_map(|buy| {
+                let sell = sells.get(buy.price.commodity.as_str())?;
+                if sell.price.sell_price <= buy.price.buy_price {
+                    return None;
+                }
+                let units = self.cargo.min(buy.price.stock);
+                let margin = (sell.price.sell_price - buy.price.buy_price) as u64;
+                Some(Hop { from: buy, to: sell, units, profit: margin * units as u64 })
+            })
+            .max_by_key(|hop| hop.profit)
+    }
+
+    fn hop_row(&self, systems: &HashMap<u64, System>, hop: &Hop, jumps: Option<f64>) -> prettytable::Row {
+        row![
+            format!("{} ({})", hop.from.station_name, systems[&hop.from.system_address].name),
+            format!("{} @ {}", hop.from.price.commodity, hop.from.price.buy_price),
+            format!("{} ({})", hop.to.station_name, systems[&hop.to.system_address].name),
+            hop.to.price.sell_price,
+            hop.units,
+            hop.profit,
+            format_jumps(jumps)
+        ]
+    }
+}
+
+// Jumps as plotted, or `-` when there's no route within the jump range.
+fn format_jumps(jumps: Option<f64>) -> String {
+    jumps.map_or_else(|| "-".into(), |j| j.to_string())
+}
@@ -47,6 +47,10 @@
 //! Usage: galos <command> ...
 //! ```
 //!
+//! Systems can be given by any of their names, including catalogue, renamed and historical
+//! names. When several systems share a name, they're listed and you're asked to pick one by
+//! its address or coordinates.
+//!
 //!
 //! ##### `galos search [OPTIONS] <query>`
 //!
@@ -60,31 +64,120 @@
 //! $ galos search --limit 50 --order factions.influence (HD*|HIP*) factions={influence<7.5%}
 //! ```
 //!
+//! Stations selling a module or ship can be found, nearest first:
+//! ```notrust
+//! $ galos search --module "5A Frame Shift Drive" --near Sol
+//! $ galos search --ship Anaconda --near Meliae --radius 100
+//! ```
+//!
 //!
 //! ##### `galos route <system> <op> <system> [<op> <system>]...`
 //! Plot routes between systems, bodies, and stations in the database.
 //!
-//! Where `op` is one of:
+//! Where `op` is one of, from loosest to tightest binding:
 //! - `A -> B` specifies a direct path from A to B
 //! - `A + B` specifies a path to both A and B, where the route could either visit
 //!     A or B first
 //! - `A | B` specifies a path to either A or B
 //!
+//! Parentheses group sub-routes, and the cheapest ordering (in jumps) is plotted. See
+//! [`route`] for details.
+//!
+//! The shell treats `->`, `|` and parentheses specially, so quote the route, or at least each
+//! operator.
+//!
+//! Examples:
+//! ```notrust
+//! $ galos route "Sol -> Alpha Centauri"
+//! $ galos route Sol '->' Alpha Centauri
+//!
+//! $ galos route "Wolf 397 -> Sol + Meliae -> Nagalinn + Sol"
+//! yields:        Wolf 397 -> Meliae -> Sol -> Nagalinn
+//! ```
+//!
+//! With `--tour`, each argument is a system to visit, and the order is optimised instead (see
+//! [`tour`]):
+//! ```notrust
+//! $ galos route --tour --loop Meliae Nagalinn "Wolf 397" Sol
+//! ```
+//!
+//! ##### `galos trade [OPTIONS] <system>`
+//! Find profitable single hop, or with `--loop` A -> B -> A, trade routes between stations
+//! within some radius of a system, using recent market data. The cargo capacity and landing pad
+//! size of the ship limit which trades are possible, and the jumps for each trade are plotted
+//! with its jump range.
+//!
 //! Examples:
 //! ```notrust
-//! $ galos route Sol -> Alpha Centauri
+//! $ galos trade --radius 30 --cargo 256 --pad L Sol
+//! $ galos trade --loop --max-age 6 Meliae
+//! ```
 //!
-//! $ galos route Wolf 397 -> Sol + Meliae -> Nagalinn + Sol
-//! yields:       Wolf 397 -> Meliae -> Sol -> Nagalinn
+//! ##### `galos faction timeline [OPTIONS] <faction>`
+//! Chart a faction's pending, active and recovering states over the last `--days`, with a row
+//! for each state in each of its systems, or only in `--system`.
+//!
+//! Examples:
+//! ```notrust
+//! $ galos faction timeline "Mother Gaia"
+//! $ galos faction timeline --days 90 --system Sol "Mother Gaia"
 //! ```
 //!
-//! TODO: Incorperate queries for both `+` and `|` nodes in the route.
+//! ##### `galos system history [OPTIONS] <system>`
+//! List every state a system has been seen in, oldest first. With `--field` only the changes to
+//! its population, security, government, allegiance or economy are listed.
+//!
+//! Examples:
+//! ```notrust
+//! $ galos system history Sol
+//! $ galos system history --field security "Wolf 397"
+//! ```
 //!
 //! ##### `galos-sync <provider>`
-//! Syncs the DB with EDDN, EDSM and/or EDDB.
+//! Syncs the DB with EDDN, EDSM, EDDB and/or Spansh.
 //!
 //! Syncing from the `eddn` provider will subscribe to its ZMQ service and continue to process
-//! events until the process is killed.
+//! events until the process is killed. When nothing arrives for `--heartbeat` seconds, or the
+//! connection fails, it reconnects with an increasing delay. Messages which can't be parsed or
+//! written are kept in the `eddn_dead_letters` table, and retried with `--reprocess`.
+//!
+//! Messages are written by `--writers` concurrent tasks, with each system's messages written
+//! in order by the same one. The throughput and the depth of each queue are printed every ten
+//! seconds.
+//!
+//! `--record FILE` appends every message received to FILE, one envelope per line. Such a file,
+//! optionally gzipped, can be ingested later with `--replay FILE`, as fast as possible or
+//! paced with `--speed FACTOR`, where 1 is real time.
+//!
+//! Messages can be filtered by `--schema`, `--software NAME[@VERSION]`, `--uploader`,
+//! `--near SYSTEM --radius LY` and `--faction`. Each may be repeated, and a message must pass
+//! every kind of filter given.
+//!
+//! ```notrust
+//! $ galos-sync eddn --schema journal --near Sol --radius 30 --faction "Mother Gaia"
+//! ```
+//!
+//! The `journal` and `eddb` providers record their progress through each file, so an
+//! interrupted import can be continued with `--resume`, which also skips finished files and
+//! continues those which have grown since, like the journal the game is writing.
+//!
+//! `galos-sync journal --follow DIR` keeps running after the import, ingesting events from the
+//! newest journal in DIR as the game writes them.
+//!
+//! `galos-sync edsm file PATH` imports one of EDSM's nightly `systemsWithCoordinates.json`,
+//! `systemsPopulated.json` or `bodies.json` dumps, which may be gzipped.
+//!
+//! `galos-sync edsm api` fetches a single system (`--system NAME`), or every system starting
+//! with a prefix (`--systems QUERY`), from EDSM's API along with its factions' influence
+//! history and its stations. Use `--url` to point it at another server.
+//!
+//! `galos-sync spansh PATH` imports Spansh's `galaxy.json.gz` or `galaxy_populated.json.gz`,
+//! including every system's bodies, stations, factions and markets.
+//!
+//! Two systems can't share a position. When a system arrives at the position of a system with
+//! another address, the existing system is kept and the new one is logged for review, along
+//! with the factions reported in it, instead of failing the import. `galos-sync doctor` lists
+//! these collisions, and any systems whose current state is missing from their history.
 //!
 //! # `galos-server`
 //! TODO
@@ -97,6 +190,9 @@
 
 use galos_db::Database;
 
+pub mod route;
+pub mod tour;
+
 pub trait Run {
     // TODO: Reture Error
     fn run(&self, db: &Database);
@@ -0,0 +1,360 @@
+//! Route expressions, as given to `galos route`.
+//!
+//! Operators, from loosest to tightest binding:
+//!
+//! - `A -> B` specifies a direct path from A to B
+//! - `A + B` specifies a path to both A and B, in either order
+//! - `A | B` specifies a path to either A or B
+//!
+//! Parentheses may be used for grouping. Operators must be separated from system names by
+//! whitespace, since names like `BD+47 2112` contain them.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Route {
+    Stop(String),
+    // `A -> B` specifies a direct path from A to B
+    Path(Box<Route>, Box<Route>),
+    // `A + B` specifies a path to both A and B, where the route could either visit
+    // A or B first
+    Both(Box<Route>, Box<Route>),
+    // `A | B` specifies a path to either A or B
+    Either(Box<Route>, Box<Route>),
+}
+
+impl Route {
+    /// Every sequence of stops this route could take, with repeated stops collapsed.
+    pub fn orderings(&self) -> Vec<Vec<&str>> {
+        let mut orderings = self.expand();
+        for ordering in orderings.iter_mut() {
+            ordering.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+        }
+        orderings.sort();
+        orderings.dedup();
+        orderings
+    }
+
+    fn expand(&self) -> Vec<Vec<&str>> {
+        match self {
+            Route::Stop(name) => vec![vec![name.as_str()]],
+            Route::Path(a, b) => concat(&a.expand(), &b.expand()),
+            Route::Both(a, b) => {
+                let (a, b) = (a.expand(), b.expand());
+                let mut orderings = concat(&a, &b);
+                orderings.extend(concat(&b, &a));
+                orderings
+            },
+            Route::Either(a, b) => {
+                let mut orderings = a.expand();
+                orderings.extend(b.expand());
+                orderings
+            },
+        }
+    }
+
+    /// Finds the cheapest ordering of this route, where `cost` gives the cost of a single leg
+    /// between two stops, or `None` when there is no path between them.
+    ///
+    /// Each leg is only costed once, no matter how many orderings share it.
+    pub fn cheapest<F>(&self, mut cost: F) -> Option<(Vec<&str>, f64)>
+        where F: FnMut(&str, &str) -> Option<f64>
+    {
+        let mut legs: HashMap<(String, String), Option<f64>> = HashMap::new();
+        let mut best: Option<(Vec<&str>, f64)> = None;
+
+        'orderings: for ordering in self.orderings() {
+            let mut total = 0.;
+            for leg in ordering.windows(2) {
+                let key = (leg[0].to_uppercase(), leg[1].to_uppercase());
+                let leg_cost = *legs.entry(key).or_insert_with(|| cost(leg[0], leg[1]));
+                match leg_cost {
+                    Some(c) => total += c,
+                    None => continue 'orderings,
+                }
+                if best.as_ref().map_or(false, |(_, b)| total >= *b) {
+                    continue 'orderings;
+                }
+            }
+
+            if best.as_ref().map_or(true, |(_, b)| total < *b) {
+                best = Some((ordering, total));
+            }
+        }
+
+        best
+    }
+}
+
+fn concat<'a>(a: &[Vec<&'a str>], b: &[Vec<&'a str>]) -> Vec<Vec<&'a str>> {
+    a.iter().flat_map(|a| {
+        b.iter().map(move |b| a.iter().chain(b.iter()).cloned().collect())
+    }).collect()
+}
+
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Route::Stop(name) => write!(f, "{}", name),
+            Route::Path(a, b) => write!(f, "({} -> {})", a, b),
+            Route::Both(a, b) => write!(f, "({} + {})", a, b),
+            Route::Either(a, b) => write!(f, "({} | {})", a, b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Name(String),
+    Arrow,
+    Plus,
+    Bar,
+    Open,
+    Close,
+}
+
+fn tokenize(s: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut name: Vec<&str> = Vec::new();
+
+    fn flush(name: &mut Vec<&str>, tokens: &mut Vec<Token>) {
+        if !name.is_empty() {
+            tokens.push(Token::Name(name.join(" ")));
+            name.clear();
+        }
+    }
+
+    for mut word in s.split_whitespace() {
+        let operator = match word {
+            "->" => Some(Token::Arrow),
+            "+"  => Some(Token::Plus),
+            "|"  => Some(Token::Bar),
+            _    => None,
+        };
+        if let Some(operator) = operator {
+            flush(&mut name, &mut tokens);
+            tokens.push(operator);
+            continue;
+        }
+
+        while let Some(rest) = word.strip_prefix('(') {
+            flush(&mut name, &mut tokens);
+            tokens.push(Token::Open);
+            word = rest;
+        }
+        let mut closes = 0;
+        while let Some(rest) = word.strip_suffix(')') {
+            closes += 1;
+            word = rest;
+        }
+        if !word.is_empty() {
+            name.push(word);
+        }
+        if closes > 0 {
+            flush(&mut name, &mut tokens);
+            tokens.extend((0..closes).map(|_| Token::Close));
+        }
+    }
+    flush(&mut name, &mut tokens);
+
+    tokens
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    // path := both ('->' both)*
+    fn path(&mut self) -> Result<Route, String> {
+        let mut route = self.both()?;
+        while self.peek() == Some(&Token::Arrow) {
+            self.next();
+            route = Route::Path(Box::new(route), Box::new(self.both()?));
+        }
+        Ok(route)
+    }
+
+    // both := either ('+' either)*
+    fn both(&mut self) -> Result<Route, String> {
+        let mut route = self.either()?;
+        while self.peek() == Some(&Token::Plus) {
+            self.next();
+            route = Route::Both(Box::new(route), Box::new(self.either()?));
+        }
+        Ok(route)
+    }
+
+    // either := stop ('|' stop)*
+    fn either(&mut self) -> Result<Route, String> {
+        let mut route = self.stop()?;
+        while self.peek() == Some(&Token::Bar) {
+            self.next();
+            route = Route::Either(Box::new(route), Box::new(self.stop()?));
+        }
+        Ok(route)
+    }
+
+    // stop := NAME | '(' path ')'
+    fn stop(&mut self) -> Result<Route, String> {
+        match self.next() {
+            Some(Token::Name(name)) => Ok(Route::Stop(name)),
+            Some(Token::Open) => {
+                let route = self.path()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(route),
+                    _ => Err("expected `)`".to_string()),
+                }
+            },
+            Some(token) => Err(format!("expected a system name, found {:?}", token)),
+            None => Err("expected a system name, found end of route".to_string()),
+        }
+    }
+}
+
+impl FromStr for Route {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { tokens: tokenize(s), position: 0 };
+        let route = parser.path()?;
+        match parser.peek() {
+            None => Ok(route),
+            Some(token) => Err(format!("unexpected {:?}", token)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use super::*;
+
+    fn stop(name: &str) -> Box<Route> {
+        Box::new(Route::Stop(name.to_string()))
+    }
+
+    #[test]
+    fn precedence() {
+        let route: Route = "A -> B + C | D".parse().unwrap();
+        assert_eq!(route, Route::Path(stop("A"),
+            Box::new(Route::Both(stop("B"), Box::new(Route::Either(stop("C"), stop("D")))))));
+        assert_eq!("A | B + C -> D".parse::<Route>().unwrap().to_string(), "(((A | B) + C) -> D)");
+        assert_eq!("A -> B -> C".parse::<Route>().unwrap().to_string(), "((A -> B) -> C)");
+    }
+
+    #[test]
+    fn parentheses() {
+        assert_eq!("(A -> B) + C".parse::<Route>().unwrap().to_string(), "((A -> B) + C)");
+        assert_eq!("A -> ((B | C))".parse::<Route>().unwrap().to_string(), "(A -> (B | C))");
+    }
+
+    #[test]
+    fn names() {
+        let route: Route = "Wolf 397 -> BD+47 2112 | (Col 285 Sector AB-C d1)".parse().unwrap();
+        assert_eq!(route, Route::Path(stop("Wolf 397"),
+            Box::new(Route::Either(stop("BD+47 2112"), stop("Col 285 Sector AB-C d1")))));
+    }
+
+    #[test]
+    fn errors() {
+        assert!("".parse::<Route>().is_err());
+        assert!("A ->".parse::<Route>().is_err());
+        assert!("-> A".parse::<Route>().is_err());
+        assert!("A + | B".parse::<Route>().is_err());
+        assert_eq!("(A -> B".parse::<Route>(), Err("expected `)`".to_string()));
+        assert_eq!("A -> B)".parse::<Route>(), Err("unexpected Close".to_string()));
+    }
+
+    #[test]
+    fn orderings() {
+        let route: Route = "A -> B + C".parse().unwrap();
+        assert_eq!(route.orderings(), vec![vec!["A", "B", "C"], vec!["A", "C", "B"]]);
+        let route: Route = "A -> B | C -> D".parse().unwrap();
+        assert_eq!(route.orderings(), vec![vec!["A", "B", "D"], vec!["A", "C", "D"]]);
+    }
+
+    #[test]
+    fn orderings_dedup() {
+        let route: Route = "Sol -> SOL -> Meliae -> Sol".parse().unwrap();
+        assert_eq!(route.orderings(), vec![vec!["Sol", "Meliae", "Sol"]]);
+        let route: Route = "A + A".parse().unwrap();
+        assert_eq!(route.orderings(), vec![vec!["A"]]);
+    }
+
+    // Stops on a line, a leg costs the distance between them.
+    fn line(a: &str, b: &str) -> Option<f64> {
+        fn position(stop: &str) -> f64 {
+            match stop {
+                "Wolf 397" => 0.,
+                "Meliae" => 1.,
+                "Sol" => 2.,
+                "Nagalinn" => 3.,
+                _ => panic!("unknown stop {}", stop),
+            }
+        }
+        Some((position(a) - position(b)).abs())
+    }
+
+    #[test]
+    fn cheapest() {
+        let route: Route = "Wolf 397 -> Sol + Meliae -> Nagalinn + Sol".parse().unwrap();
+        let (ordering, cost) = route.cheapest(line).unwrap();
+        assert_eq!(ordering, vec!["Wolf 397", "Meliae", "Sol", "Nagalinn"]);
+        assert_eq!(cost, 3.);
+    }
+
+    #[test]
+    fn cheapest_costs_each_leg_once() {
+        let legs = RefCell::new(Vec::new());
+        let route: Route = "Wolf 397 -> Sol + Meliae + Nagalinn".parse().unwrap();
+        route.cheapest(|a, b| {
+            legs.borrow_mut().push((a.to_string(), b.to_string()));
+            line(a, b)
+        });
+        let mut legs = legs.into_inner();
+        let costed = legs.len();
+        legs.sort();
+        legs.dedup();
+        assert_eq!(costed, legs.len());
+    }
+
+    #[test]
+    fn cheapest_prunes() {
+        let legs = RefCell::new(Vec::new());
+        let route: Route = "A -> B | C -> D".parse().unwrap();
+        let cheapest = route.cheapest(|a, b| {
+            legs.borrow_mut().push(format!("{} -> {}", a, b));
+            match (a, b) {
+                ("A", "B") | ("B", "D") => Some(1.),
+                ("A", "C") => Some(10.),
+                _ => Some(0.),
+            }
+        });
+        assert_eq!(cheapest, Some((vec!["A", "B", "D"], 2.)));
+        // A -> C alone costs more than the best ordering, so C -> D is never costed.
+        assert_eq!(legs.into_inner(), vec!["A -> B", "B -> D", "A -> C"]);
+    }
+
+    #[test]
+    fn cheapest_without_path() {
+        let route: Route = "A -> B | C".parse().unwrap();
+        let cheapest = route.cheapest(|_, b| if b == "B" { None } else { Some(5.) });
+        assert_eq!(cheapest, Some((vec!["A", "C"], 5.)));
+        let route: Route = "A -> B".parse().unwrap();
+        assert_eq!(route.cheapest(|_, _| None), None);
+    }
+}
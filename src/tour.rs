@@ -0,0 +1,137 @@
+//! Visiting order optimisation for multi-stop tours, as used by `galos route --tour`.
+//!
+//! Finding the best order is the travelling salesman problem, so this settles for a near
+//! optimal one: a nearest neighbor tour, improved with 2-opt until no reversal of a run of
+//! stops makes it any cheaper.
+
+/// Finds a cheap order to visit stops `0..n`, where `cost(a, b)` is the cost of travelling
+/// from stop `a` to `b`.
+///
+/// The tour always starts at stop `0`. When `closed` it returns there at the end, otherwise it
+/// ends at stop `n - 1`. The returned order doesn't repeat the start of a closed tour.
+pub fn optimise<F>(n: usize, closed: bool, cost: F) -> Vec<usize>
+    where F: Fn(usize, usize) -> f64
+{
+    if n < 3 {
+        return (0..n).collect();
+    }
+
+    let mut order = nearest_neighbor(n, closed, &cost);
+    let mut best = total(&order, closed, &cost);
+
+    // The first stop, and the last stop of an open tour, never move.
+    let last = if closed { n - 1 } else { n - 2 };
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for i in 1..last {
+            for j in (i + 1)..=last {
+                order[i..=j].reverse();
+                let candidate = total(&order, closed, &cost);
+                if candidate < best {
+                    best = candidate;
+                    improved = true;
+                } else {
+                    order[i..=j].reverse();
+                }
+            }
+        }
+    }
+
+    order
+}
+
+/// The total cost of visiting `order`, including the return to the start of a closed tour.
+pub fn total<F>(order: &[usize], closed: bool, cost: F) -> f64
+    where F: Fn(usize, usize) -> f64
+{
+    let mut total = order.windows(2).map(|w| cost(w[0], w[1])).sum();
+    if closed && order.len() > 1 {
+        total += cost(order[order.len() - 1], order[0]);
+    }
+    total
+}
+
+fn nearest_neighbor<F>(n: usize, closed: bool, cost: F) -> Vec<usize>
+    where F: Fn(usize, usize) -> f64
+{
+    let mut order = vec![0];
+    let mut remaining: Vec<usize> = if closed { (1..n).collect() } else { (1..n - 1).collect() };
+
+    while !remaining.is_empty() {
+        let current = order[order.len() - 1];
+        let (i, _) = remaining.iter().enumerate()
+            .min_by(|(_, &a), (_, &b)| {
+                cost(current, a).partial_cmp(&cost(current, b)).expect("comparable costs")
+            })
+            .expect("remaining stops");
+        order.push(remaining.swap_remove(i));
+    }
+
+    if !closed {
+        order.push(n - 1);
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn on_line(positions: &[f64]) -> impl Fn(usize, usize) -> f64 + '_ {
+        move |a, b| (positions[a] - positions[b]).abs()
+    }
+
+    #[test]
+    fn small() {
+        assert_eq!(optimise(0, false, |_, _| 1.), Vec::<usize>::new());
+        assert_eq!(optimise(2, true, |_, _| 1.), vec![0, 1]);
+    }
+
+    #[test]
+    fn total_closed() {
+        let cost = on_line(&[0., 1., 3.]);
+        assert_eq!(total(&[0, 1, 2], false, &cost), 3.);
+        assert_eq!(total(&[0, 1, 2], true, &cost), 6.);
+    }
+
+    #[test]
+    fn open() {
+        let positions = [0., 5., 1., 4., 2., 10.];
+        let order = optimise(positions.len(), false, on_line(&positions));
+        assert_eq!(order, vec![0, 2, 4, 3, 1, 5]);
+    }
+
+    #[test]
+    fn open_keeps_ends() {
+        // Visiting 1 last would be cheaper, but the tour must end at 2.
+        let positions = [0., 10., 5.];
+        let order = optimise(positions.len(), false, on_line(&positions));
+        assert_eq!(order, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn closed() {
+        // Corners of a square, which is cheapest visited around its edge.
+        let corners = [(0., 0.), (1., 1.), (1., 0.), (0., 1.)];
+        let cost = |a: usize, b: usize| {
+            let ((x1, y1), (x2, y2)): ((f64, f64), (f64, f64)) = (corners[a], corners[b]);
+            ((x1 - x2).powi(2) + (y1 - y2).powi(2)).sqrt()
+        };
+        let order = optimise(corners.len(), true, cost);
+        assert_eq!(order[0], 0);
+        assert_eq!(total(&order, true, cost), 4.);
+    }
+
+    #[test]
+    fn two_opt_improves_nearest_neighbor() {
+        let positions = [0., 1., -2., 4.5];
+        let cost = on_line(&positions);
+        let greedy = nearest_neighbor(positions.len(), true, &cost);
+        assert_eq!(greedy, vec![0, 1, 2, 3]);
+        assert_eq!(total(&greedy, true, &cost), 15.);
+
+        let order = optimise(positions.len(), true, &cost);
+        assert_eq!(total(&order, true, &cost), 13.);
+    }
+}